@@ -0,0 +1,75 @@
+//! Signs `editors.json` for publishing at a URL the installer is pointed at with `--manifest-url`.
+//!
+//! ```sh
+//! # Once: create the signing key and add the printed public key to `TRUSTED_KEYS` in manifest.rs
+//! cargo run --example sign_manifest -- keygen manifest-key.txt
+//! # On every manifest change, after bumping its "version"
+//! cargo run --example sign_manifest -- sign manifest-key.txt src/editor_plugins/editors.json > signed.json
+//! ```
+//!
+//! The key file holds the base64 ed25519 seed. Keep it out of the repo: whoever has it can
+//! publish editor manifests every installed copy will accept.
+
+use std::fs;
+use std::io::Write;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use color_eyre::{Result, eyre::eyre};
+use ed25519_dalek::{Signer, SigningKey};
+use rand::Rng;
+
+fn read_key(path: &str) -> Result<SigningKey> {
+    let seed = BASE64_STANDARD
+        .decode(fs::read_to_string(path)?.trim())
+        .map_err(|e| eyre!("Invalid key file {}: {}", path, e))?;
+    let seed: [u8; 32] = seed
+        .try_into()
+        .map_err(|_| eyre!("Key file {} should hold a 32-byte seed", path))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn keygen(path: &str) -> Result<()> {
+    let seed: [u8; 32] = rand::rng().random();
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| eyre!("Failed to create {}: {}", path, e))?
+        .write_all(BASE64_STANDARD.encode(seed).as_bytes())?;
+
+    let public_key = SigningKey::from_bytes(&seed).verifying_key().to_bytes();
+    let bytes: Vec<String> = public_key.iter().map(|b| format!("{b:#04x}")).collect();
+    println!("[");
+    for row in bytes.chunks(16) {
+        println!("    {},", row.join(", "));
+    }
+    println!("],");
+    Ok(())
+}
+
+fn sign(key_path: &str, manifest_path: &str) -> Result<()> {
+    let key = read_key(key_path)?;
+    let manifest = fs::read_to_string(manifest_path)?;
+    serde_json::from_str::<serde_json::Value>(&manifest)
+        .map_err(|e| eyre!("Invalid {}: {}", manifest_path, e))?;
+
+    let signature = key.sign(manifest.as_bytes());
+    let signed = serde_json::json!({
+        "manifest": manifest,
+        "signature": BASE64_STANDARD.encode(signature.to_bytes()),
+    });
+    println!("{}", serde_json::to_string_pretty(&signed)?);
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["keygen", key] => keygen(key),
+        ["sign", key, manifest] => sign(key, manifest),
+        _ => Err(eyre!(
+            "Usage: sign_manifest keygen KEY_FILE | sign KEY_FILE MANIFEST"
+        )),
+    }
+}
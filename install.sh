@@ -17,7 +17,7 @@ BINARY_NAME="hackatime_setup"
 
 # Check for API key argument
 if [ $# -lt 1 ]; then
-    echo "Usage: $0 <api-key> [api-url]"
+    echo "Usage: $0 <api-key> [api-url] [hackatime_setup args...]"
     echo "  curl -fsSL https://raw.githubusercontent.com/$REPO/main/install.sh | bash -s -- YOUR_API_KEY"
     exit 1
 fi
@@ -68,8 +68,9 @@ curl -sL "$DOWNLOAD_URL" -o "$TEMP_DIR/$ASSET_NAME"
 tar -xzf "$TEMP_DIR/$ASSET_NAME" -C "$TEMP_DIR"
 chmod +x "$TEMP_DIR/$BINARY_NAME"
 
+# Anything after the API URL, such as the `remote` subcommand, is passed through
 if [ -n "$API_URL" ]; then
-    "$TEMP_DIR/$BINARY_NAME" --key "$API_KEY" --api-url "$API_URL"
+    "$TEMP_DIR/$BINARY_NAME" --key "$API_KEY" --api-url "$API_URL" "${@:3}"
 else
-    "$TEMP_DIR/$BINARY_NAME" --key "$API_KEY"
+    "$TEMP_DIR/$BINARY_NAME" --key "$API_KEY" "${@:3}"
 fi
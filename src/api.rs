@@ -0,0 +1,711 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use color_eyre::{Result, eyre::eyre};
+use colored::Colorize;
+use reqwest::Url;
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+
+/// Servers people commonly point WakaTime plugins at, by the alias they can be given as
+struct KnownServer {
+    alias: &'static str,
+    hosts: &'static [&'static str],
+    base_url: &'static str,
+}
+
+const KNOWN_SERVERS: &[KnownServer] = &[
+    KnownServer {
+        alias: "hackatime",
+        hosts: &["hackatime.hackclub.com"],
+        base_url: "https://hackatime.hackclub.com/api/hackatime/v1",
+    },
+    KnownServer {
+        alias: "wakatime",
+        hosts: &["wakatime.com", "api.wakatime.com"],
+        base_url: "https://api.wakatime.com/api/v1",
+    },
+    KnownServer {
+        alias: "wakapi",
+        hosts: &["wakapi.dev"],
+        base_url: "https://wakapi.dev/api/compat/wakatime/v1",
+    },
+];
+
+/// Where self-hosted Hackatime, Wakapi and WakaTime-compatible servers serve the API
+const BASE_PATHS: &[&str] = &["/api/hackatime/v1", "/api/compat/wakatime/v1", "/api/v1"];
+
+/// Cleans up an API URL as typed: expands aliases like `wakapi`, adds a missing `https://`,
+/// drops trailing slashes and fills in the base path of known servers given as a bare host
+pub fn normalize_api_url(input: &str) -> Result<String> {
+    let input = input.trim();
+    if let Some(server) = KNOWN_SERVERS
+        .iter()
+        .find(|s| s.alias.eq_ignore_ascii_case(input))
+    {
+        return Ok(server.base_url.to_string());
+    }
+
+    // `localhost:3000` would otherwise parse with `localhost` as the scheme. Local servers
+    // rarely have a certificate, so they get plain http
+    let with_scheme = if input.contains("://") {
+        input.to_string()
+    } else {
+        let host = Url::parse(&format!("http://{input}"))
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string));
+        match host {
+            Some(host) if is_local(&host) => format!("http://{input}"),
+            _ => format!("https://{input}"),
+        }
+    };
+    let url = Url::parse(&with_scheme).map_err(|e| eyre!("Invalid API URL {}: {}", input, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(eyre!("API URL {} must start with https://", input));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| eyre!("API URL {} has no host", input))?;
+
+    let path = url.path().trim_end_matches('/');
+    if path.is_empty()
+        && let Some(server) = KNOWN_SERVERS.iter().find(|s| s.hosts.contains(&host))
+    {
+        return Ok(server.base_url.to_string());
+    }
+
+    let origin = url.origin().ascii_serialization();
+    Ok(format!("{origin}{path}"))
+}
+
+/// Whether `api_url` resolves to WakaTime itself, whose keys carry a `waka_` prefix
+pub fn is_wakatime(api_url: &str) -> bool {
+    normalize_api_url(api_url).is_ok_and(|url| {
+        KNOWN_SERVERS
+            .iter()
+            .any(|s| s.alias == "wakatime" && s.base_url == url)
+    })
+}
+
+/// Whether `host` is this machine or on the local network, where plain http is fine
+fn is_local(host: &str) -> bool {
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        Ok(IpAddr::V6(ip)) => ip.is_loopback(),
+        Err(_) => host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local"),
+    }
+}
+
+/// Normalizes an API URL and checks the server actually serves the API there, trying the
+/// usual base paths if it doesn't. Servers that can't be reached are given the benefit of the
+/// doubt, so setting up offline still works.
+pub fn resolve_api_url(input: &str, api_key: &str, proxy: Option<&str>) -> Result<String> {
+    let api_url = normalize_api_url(input)?;
+    let url = Url::parse(&api_url)?;
+    let host = url.host_str().unwrap_or_default();
+
+    if url.scheme() == "http" && !is_local(host) {
+        eprintln!(
+            "{} {} uses plain http, so your API key would be sent unencrypted. Use https:// if the server supports it.",
+            "Warning:".yellow(),
+            api_url
+        );
+    }
+    if KNOWN_SERVERS.iter().any(|s| s.base_url == api_url) {
+        return Ok(api_url);
+    }
+
+    let origin = url.origin().ascii_serialization();
+    let mut candidates = vec![api_url.clone()];
+    candidates.extend(
+        BASE_PATHS
+            .iter()
+            .map(|path| format!("{origin}{path}"))
+            .filter(|candidate| *candidate != api_url),
+    );
+
+    for candidate in &candidates {
+        let serves_api = HackatimeClient::new(candidate, api_key, proxy)?.serves_api();
+        match serves_api {
+            Ok(true) => {
+                if *candidate != api_url {
+                    eprintln!(
+                        "{}",
+                        format!("Found the API at {candidate}, using that instead of {api_url}")
+                            .dimmed()
+                    );
+                }
+                return Ok(candidate.clone());
+            }
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("{} {}, using it as given", "Warning:".yellow(), e);
+                return Ok(api_url);
+            }
+        }
+    }
+
+    Err(eyre!(
+        "{} doesn't serve a WakaTime-compatible API. Pass the full API URL, e.g. {}{}",
+        api_url,
+        origin,
+        BASE_PATHS[0]
+    ))
+}
+
+/// The server turned the API key down, as opposed to being unreachable or broken
+#[derive(Debug)]
+pub struct KeyRejected {
+    base_url: String,
+}
+
+impl fmt::Display for KeyRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rejected the API key", self.base_url)
+    }
+}
+
+impl std::error::Error for KeyRejected {}
+
+/// A client for the WakaTime-compatible API that Hackatime serves
+pub struct HackatimeClient {
+    base_url: String,
+    api_key: String,
+    http: Client,
+}
+
+#[derive(Serialize)]
+pub struct Heartbeat {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub time: u64,
+    pub entity: String,
+    pub language: String,
+}
+
+/// Responses come wrapped in `{"data": ...}`
+#[derive(Deserialize)]
+struct Data<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+pub struct User {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+impl User {
+    pub fn describe(&self) -> String {
+        match (&self.display_name, &self.username) {
+            (Some(name), Some(username)) if name != username => format!("{name} ({username})"),
+            (Some(name), _) | (None, Some(name)) => name.clone(),
+            (None, None) => "unknown user".to_string(),
+        }
+    }
+}
+
+/// Today's total, as shown in editor status bars
+#[derive(Deserialize)]
+pub struct StatusBar {
+    pub grand_total: GrandTotal,
+}
+
+#[derive(Deserialize)]
+pub struct GrandTotal {
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub total_seconds: f64,
+}
+
+#[derive(Deserialize)]
+pub struct Stats {
+    #[serde(default)]
+    pub human_readable_total: Option<String>,
+    #[serde(default)]
+    pub languages: Vec<StatsEntry>,
+    #[serde(default)]
+    pub editors: Vec<StatsEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct StatsEntry {
+    pub name: String,
+}
+
+/// An HTTP client builder with the user agent and timeouts every request this tool makes
+/// should have. Downloads can raise the overall timeout.
+pub fn client_builder() -> ClientBuilder {
+    Client::builder()
+        .user_agent(concat!("hackatime-setup/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(15))
+}
+
+impl HackatimeClient {
+    /// `proxy` is an HTTP(S) proxy URL like WakaTime's `proxy` setting. Without one, the usual
+    /// `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` variables apply.
+    pub fn new(base_url: &str, api_key: &str, proxy: Option<&str>) -> Result<Self> {
+        let mut builder = client_builder();
+        if let Some(proxy) = proxy {
+            let proxy =
+                reqwest::Proxy::all(proxy).map_err(|e| eyre!("Invalid proxy {}: {}", proxy, e))?;
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(Self {
+            base_url: base_url.trim().trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            http: builder.build()?,
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    /// WakaTime wants API keys as Basic auth with the base64 key; Hackatime and Wakapi accept
+    /// that too
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        request.header(
+            reqwest::header::AUTHORIZATION,
+            format!("Basic {}", BASE64_STANDARD.encode(&self.api_key)),
+        )
+    }
+
+    /// Sends the request with Basic auth, retrying with the key as a Bearer token if that's
+    /// turned down, for servers that only take the key the way earlier versions sent it
+    fn execute(&self, request: RequestBuilder) -> Result<Response> {
+        let retry = request.try_clone();
+        let response = self
+            .authorized(request)
+            .send()
+            .map_err(|e| eyre!("Could not reach {}: {}", self.base_url, e))?;
+        match retry {
+            Some(retry) if response.status() == reqwest::StatusCode::UNAUTHORIZED => retry
+                .bearer_auth(&self.api_key)
+                .send()
+                .map_err(|e| eyre!("Could not reach {}: {}", self.base_url, e)),
+            _ => Ok(response),
+        }
+    }
+
+    /// Sends the request and decodes the JSON response, turning HTTP errors into readable ones
+    fn send<T: DeserializeOwned>(&self, request: RequestBuilder, path: &str) -> Result<T> {
+        let response = self.execute(request)?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(KeyRejected {
+                base_url: self.base_url.clone(),
+            }
+            .into());
+        }
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(eyre!("{} failed ({}): {}", self.url(path), status, body));
+        }
+
+        response
+            .json()
+            .map_err(|e| eyre!("Unexpected response from {}: {}", self.url(path), e))
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.send(self.http.get(self.url(path)), path)
+    }
+
+    fn post<B: Serialize + ?Sized>(&self, path: &str, body: &B) -> Result<()> {
+        self.send::<IgnoredAny>(self.http.post(self.url(path)).json(body), path)
+            .map(|_| ())
+    }
+
+    /// Whether the API lives at this base URL. Anything but a 404 or a web page counts, so a
+    /// wrong key doesn't look like a wrong URL.
+    pub fn serves_api(&self) -> Result<bool> {
+        let response = self
+            .authorized(self.http.get(self.url("users/current/statusbar/today")))
+            .send()
+            .map_err(|e| eyre!("Could not reach {}: {}", self.base_url, e))?;
+
+        let status = response.status();
+        if matches!(
+            status,
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+        ) {
+            return Ok(true);
+        }
+        let json = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|t| t.to_str().ok())
+            .is_some_and(|t| t.contains("json"));
+        Ok(status != reqwest::StatusCode::NOT_FOUND && json)
+    }
+
+    pub fn current_user(&self) -> Result<User> {
+        self.get::<Data<User>>("users/current").map(|d| d.data)
+    }
+
+    pub fn send_heartbeat(&self, heartbeat: &Heartbeat) -> Result<()> {
+        self.post("users/current/heartbeats", heartbeat)
+    }
+
+    /// Nothing sends more than the one test heartbeat yet
+    #[allow(dead_code)]
+    pub fn send_heartbeats(&self, heartbeats: &[Heartbeat]) -> Result<()> {
+        self.post("users/current/heartbeats.bulk", heartbeats)
+    }
+
+    pub fn status_bar_today(&self) -> Result<StatusBar> {
+        self.get::<Data<StatusBar>>("users/current/statusbar/today")
+            .map(|d| d.data)
+    }
+
+    /// Stats over a range like `last_7_days`
+    pub fn stats(&self, range: &str) -> Result<Stats> {
+        self.get::<Data<Stats>>(&format!("users/current/stats/{range}"))
+            .map(|d| d.data)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    const KEY: &str = "12345678-1234-4234-8234-123456789abc";
+
+    /// A request as the mock server saw it
+    pub(crate) struct Request {
+        /// e.g. `GET /api/v1/users/current HTTP/1.1`
+        pub line: String,
+        /// Lowercased names
+        headers: Vec<(String, String)>,
+        pub body: String,
+    }
+
+    impl Request {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// Serves `responses` (status, content type, body) to one request each, then hands back
+    /// the requests it got
+    pub(crate) fn mock_server<B: AsRef<str> + Send + 'static>(
+        responses: Vec<(u16, &'static str, B)>,
+    ) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|(status, content_type, body)| {
+                    let body = body.as_ref();
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let mut headers = Vec::new();
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        let Some((name, value)) = header.trim_end().split_once(':') else {
+                            break;
+                        };
+                        headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+                    }
+                    let length = headers
+                        .iter()
+                        .find(|(n, _)| n == "content-length")
+                        .map_or(0, |(_, v)| v.parse().unwrap());
+                    let mut request_body = vec![0; length];
+                    reader.read_exact(&mut request_body).unwrap();
+
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {status} Mock\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+
+                    Request {
+                        line: line.trim_end().to_string(),
+                        headers,
+                        body: String::from_utf8(request_body).unwrap(),
+                    }
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn client_builder_sets_user_agent() {
+        let (url, server) = mock_server(vec![(200, "text/plain", "ok")]);
+        client_builder().build().unwrap().get(&url).send().unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(
+            requests[0]
+                .header("user-agent")
+                .unwrap()
+                .starts_with("hackatime-setup/")
+        );
+    }
+
+    #[test]
+    fn joins_urls_and_sends_credentials() {
+        let (url, server) = mock_server(vec![(
+            200,
+            "application/json",
+            r#"{"data": {"username": "orpheus", "display_name": "Orpheus"}}"#,
+        )]);
+        let client = HackatimeClient::new(&format!("{url}/api/v1/"), KEY, None).unwrap();
+
+        let user = client.current_user().unwrap();
+        assert_eq!(user.describe(), "Orpheus (orpheus)");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].line, "GET /api/v1/users/current HTTP/1.1");
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some(format!("Basic {}", BASE64_STANDARD.encode(KEY)).as_str())
+        );
+        assert_eq!(
+            requests[0].header("user-agent"),
+            Some(concat!("hackatime-setup/", env!("CARGO_PKG_VERSION")))
+        );
+    }
+
+    fn heartbeat() -> Heartbeat {
+        Heartbeat {
+            kind: "file".to_string(),
+            time: 1700000000,
+            entity: "test.txt".to_string(),
+            language: "Text".to_string(),
+        }
+    }
+
+    #[test]
+    fn posts_single_heartbeat() {
+        let (url, server) = mock_server(vec![(201, "application/json", r#"{"data": {}}"#)]);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        client.send_heartbeat(&heartbeat()).unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].line, "POST /users/current/heartbeats HTTP/1.1");
+        let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"type": "file", "time": 1700000000, "entity": "test.txt", "language": "Text"})
+        );
+    }
+
+    #[test]
+    fn retries_rejected_basic_auth_as_bearer() {
+        let (url, server) = mock_server(vec![
+            (401, "application/json", r#"{"error": "bad auth"}"#),
+            (201, "application/json", r#"{"data": {}}"#),
+        ]);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        client.send_heartbeat(&heartbeat()).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(
+            requests[0]
+                .header("authorization")
+                .unwrap()
+                .starts_with("Basic ")
+        );
+        assert_eq!(
+            requests[1].header("authorization"),
+            Some(format!("Bearer {KEY}").as_str())
+        );
+        assert_eq!(requests[1].body, requests[0].body);
+    }
+
+    #[test]
+    fn posts_heartbeats_in_bulk() {
+        let (url, server) = mock_server(vec![(201, "application/json", r#"{"responses": []}"#)]);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        client.send_heartbeats(&[heartbeat()]).unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests[0].line,
+            "POST /users/current/heartbeats.bulk HTTP/1.1"
+        );
+        let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([{"type": "file", "time": 1700000000, "entity": "test.txt", "language": "Text"}])
+        );
+    }
+
+    #[test]
+    fn decodes_status_bar_and_stats() {
+        let (url, _server) = mock_server(vec![
+            (
+                200,
+                "application/json",
+                r#"{"data": {"grand_total": {"text": "1 hr 5 mins", "total_seconds": 3900.0}}}"#,
+            ),
+            (
+                200,
+                "application/json",
+                r#"{"data": {"human_readable_total": "9 hrs", "languages": [{"name": "Rust"}], "editors": [{"name": "VS Code"}]}}"#,
+            ),
+        ]);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        let today = client.status_bar_today().unwrap();
+        assert_eq!(today.grand_total.text, "1 hr 5 mins");
+        assert_eq!(today.grand_total.total_seconds, 3900.0);
+
+        let stats = client.stats("last_7_days").unwrap();
+        assert_eq!(stats.human_readable_total.as_deref(), Some("9 hrs"));
+        assert_eq!(stats.languages[0].name, "Rust");
+        assert_eq!(stats.editors[0].name, "VS Code");
+    }
+
+    #[test]
+    fn rejected_key_is_its_own_error() {
+        let (url, _server) = mock_server(vec![
+            (401, "application/json", r#"{"error": "no"}"#),
+            (401, "application/json", r#"{"error": "no"}"#),
+        ]);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        let error = client.current_user().err().unwrap();
+        assert!(error.is::<KeyRejected>());
+    }
+
+    #[test]
+    fn reports_http_errors_with_body() {
+        let (url, _server) =
+            mock_server(vec![(404, "application/json", r#"{"error": "not found"}"#)]);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        let error = client.current_user().err().unwrap();
+        assert!(!error.is::<KeyRejected>());
+        let message = error.to_string();
+        assert!(message.contains("404"), "{message}");
+        assert!(message.contains("not found"), "{message}");
+    }
+
+    #[test]
+    fn reports_non_json_responses() {
+        let (url, _server) = mock_server(vec![(200, "text/html", "<html>Welcome</html>")]);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        let error = client.current_user().err().unwrap();
+        assert!(
+            error.to_string().starts_with("Unexpected response"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn normalizes_api_urls() {
+        let cases = [
+            (
+                "hackatime",
+                "https://hackatime.hackclub.com/api/hackatime/v1",
+            ),
+            ("WakaTime", "https://api.wakatime.com/api/v1"),
+            ("wakapi", "https://wakapi.dev/api/compat/wakatime/v1"),
+            ("https://wakatime.com/", "https://api.wakatime.com/api/v1"),
+            (
+                "hackatime.hackclub.com",
+                "https://hackatime.hackclub.com/api/hackatime/v1",
+            ),
+            ("localhost:3000", "http://localhost:3000"),
+            ("192.168.1.20:3000/api", "http://192.168.1.20:3000/api"),
+            (
+                "wakapi.example.com:8080/api/",
+                "https://wakapi.example.com:8080/api",
+            ),
+            (
+                "http://127.0.0.1:3000/api/v1//",
+                "http://127.0.0.1:3000/api/v1",
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(normalize_api_url(input).unwrap(), expected, "{input}");
+        }
+        assert!(normalize_api_url("ftp://example.com").is_err());
+    }
+
+    #[test]
+    fn tells_wakatime_urls() {
+        assert!(is_wakatime("wakatime"));
+        assert!(is_wakatime("https://wakatime.com"));
+        assert!(is_wakatime("api.wakatime.com/api/v1/"));
+        assert!(!is_wakatime("hackatime"));
+        assert!(!is_wakatime("https://wakatime.example.com"));
+    }
+
+    #[test]
+    fn tells_local_hosts() {
+        for host in [
+            "localhost",
+            "wakapi.localhost",
+            "nas.local",
+            "127.0.0.1",
+            "192.168.1.5",
+            "[::1]",
+        ] {
+            assert!(is_local(host), "{host}");
+        }
+        for host in ["example.com", "8.8.8.8", "localhost.example.com"] {
+            assert!(!is_local(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn finds_api_base_path() {
+        let (url, server) = mock_server(vec![
+            (200, "text/html", "<html>Welcome</html>"),
+            (401, "application/json", r#"{"error": "unauthorized"}"#),
+        ]);
+
+        let api_url = resolve_api_url(&url, KEY, None).unwrap();
+
+        assert_eq!(api_url, format!("{url}/api/hackatime/v1"));
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests[1].line,
+            "GET /api/hackatime/v1/users/current/statusbar/today HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn unreachable_server_is_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = HackatimeClient::new(&url, KEY, None).unwrap();
+
+        let error = client.current_user().err().unwrap();
+        assert!(error.to_string().starts_with("Could not reach"), "{error}");
+    }
+}
@@ -1,39 +1,326 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use color_eyre::{Result, eyre::eyre};
 use colored::Colorize;
+use serde::Deserialize;
 use which::which;
 
-use super::EditorPlugin;
+use super::sandbox::Sandbox;
 use super::utils::is_process_running;
+use super::{EditorPlugin, SetupOptions, jetbrains_zip, launchers, nix, toolbox};
+use crate::journal;
 
+#[derive(Clone, Deserialize)]
 pub struct JetBrainsFamily {
-    pub name: &'static str,
-    pub product_codes: &'static [&'static str],
-    pub cli_command: &'static str,
-    #[allow(dead_code)]
-    pub macos_app_names: &'static [&'static str],
+    pub name: String,
+    /// Config dir name prefixes, e.g. "PyCharm" for `PyCharm2025.2`
+    pub product_codes: Vec<String>,
+    /// Launcher names to look for, in order of preference
+    pub cli_commands: Vec<String>,
+    #[serde(default)]
+    #[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+    pub macos_app_names: Vec<String>,
+    /// Folder the config dirs live under, "Google" for Android Studio
+    #[serde(default = "default_vendor")]
+    pub vendor: String,
+    /// Flatpak app ids, e.g. "com.jetbrains.PyCharm-Community"
+    #[serde(default)]
+    pub flatpak_ids: Vec<String>,
+    /// Snap names, e.g. "pycharm-community"
+    #[serde(default)]
+    pub snap_names: Vec<String>,
+}
+
+fn default_vendor() -> String {
+    "JetBrains".to_string()
+}
+
+/// The parts of an IDE's `product-info.json` we care about
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProductInfo {
+    version: String,
+    build_number: String,
+    product_code: String,
+    /// Name of the config dir this installation uses, e.g. `PyCharm2025.2`
+    data_directory_name: String,
+    #[serde(default)]
+    launch: Vec<LaunchInfo>,
+    /// Directory the file was read from; `launcherPath` is relative to it
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchInfo {
+    os: String,
+    launcher_path: String,
+}
+
+impl ProductInfo {
+    fn read(dir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(dir.join("product-info.json")).ok()?;
+        let mut info: Self = serde_json::from_str(&content).ok()?;
+        info.dir = dir.to_path_buf();
+        Some(info)
+    }
+
+    /// Reads `product-info.json` from an installation root (or a macOS `.app` bundle)
+    fn in_root(root: &Path) -> Option<Self> {
+        Self::read(root).or_else(|| Self::read(&root.join("Contents/Resources")))
+    }
+
+    /// Reads `product-info.json` from the installation the launcher at `cli_path` belongs to
+    fn for_cli(cli_path: &Path) -> Option<Self> {
+        let cli_path = std::fs::canonicalize(cli_path).ok()?;
+        // <root>/bin/<cli> on Linux and Windows, <App>.app/Contents/MacOS/<cli> on macOS
+        let root = cli_path.parent()?.parent()?;
+        Self::read(root).or_else(|| Self::read(&root.join("Resources")))
+    }
+
+    /// The launcher this installation declares for the current OS
+    fn launcher(&self) -> Option<PathBuf> {
+        let os = if cfg!(target_os = "macos") {
+            "macOS"
+        } else if cfg!(target_os = "windows") {
+            "Windows"
+        } else {
+            "Linux"
+        };
+
+        self.launch
+            .iter()
+            .filter(|l| l.os.eq_ignore_ascii_case(os))
+            .map(|l| self.dir.join(&l.launcher_path))
+            .find(|path| path.exists())
+    }
+
+    /// Build string the plugin marketplace understands, e.g. `IU-242.23339.11`
+    fn marketplace_build(&self) -> String {
+        format!("{}-{}", self.product_code, self.build_number)
+    }
+}
+
+/// An installation of a JetBrains IDE and the launcher to drive it with
+struct Installation {
+    info: ProductInfo,
+    launcher: Option<PathBuf>,
+    eap: bool,
 }
 
 impl JetBrainsFamily {
-    fn config_dirs(&self) -> Vec<PathBuf> {
+    /// One target per installed version, each paired with its own config dir and launcher,
+    /// plus one per Flatpak or Snap install. Config dirs left behind by uninstalled versions
+    /// are skipped.
+    pub fn targets(self, options: &SetupOptions) -> Vec<Box<dyn EditorPlugin>> {
+        let config_dirs = self.config_dirs(None);
+        let installations = self.installations();
+
+        let mut targets: Vec<JetBrainsTarget> = installations
+            .into_iter()
+            .map(|install| {
+                let info = install.info;
+                let config_dir = config_dirs
+                    .iter()
+                    .find(|dir| {
+                        dir.file_name()
+                            .is_some_and(|n| *n == *info.data_directory_name)
+                    })
+                    .cloned();
+                let version = if install.eap {
+                    format!("{} EAP", info.version)
+                } else {
+                    info.version.clone()
+                };
+                JetBrainsTarget {
+                    family: self.clone(),
+                    sandbox: None,
+                    version: Some(version),
+                    config_dir,
+                    launcher: install.launcher,
+                    product_info: Some(info),
+                    plugin_zip: options.jetbrains_plugin_zip.clone(),
+                    unpack: options.jetbrains_unpack,
+                    nix_snippet_dir: options.nix_snippet_dir.clone(),
+                }
+            })
+            .collect();
+
+        // Layouts we can't read product-info.json from (snap wrappers, custom scripts): fall
+        // back to whatever CLI is on PATH and the newest config dir
+        if targets.is_empty() {
+            let launcher = self.find_cli();
+            let config_dir = self.newest_config_dir(&config_dirs);
+            if launcher.is_some() || config_dir.is_some() {
+                targets.push(JetBrainsTarget {
+                    version: config_dir.as_deref().and_then(|d| self.version_suffix(d)),
+                    product_info: launcher.as_deref().and_then(ProductInfo::for_cli),
+                    family: self.clone(),
+                    sandbox: None,
+                    config_dir,
+                    launcher,
+                    plugin_zip: options.jetbrains_plugin_zip.clone(),
+                    unpack: options.jetbrains_unpack,
+                    nix_snippet_dir: options.nix_snippet_dir.clone(),
+                });
+            }
+        }
+
+        // The sandbox hides the install itself, so go by its config dirs and drive the IDE
+        // through the package manager
+        for sandbox in Sandbox::installed(&self.flatpak_ids, &self.snap_names) {
+            let config_dir = self.newest_config_dir(&self.config_dirs(Some(&sandbox)));
+            targets.push(JetBrainsTarget {
+                family: self.clone(),
+                version: config_dir.as_deref().and_then(|d| self.version_suffix(d)),
+                sandbox: Some(sandbox),
+                config_dir,
+                launcher: None,
+                product_info: None,
+                plugin_zip: options.jetbrains_plugin_zip.clone(),
+                unpack: options.jetbrains_unpack,
+                nix_snippet_dir: options.nix_snippet_dir.clone(),
+            });
+        }
+
+        targets
+            .into_iter()
+            .map(|t| -> Box<dyn EditorPlugin> { Box::new(t) })
+            .collect()
+    }
+
+    fn newest_config_dir(&self, config_dirs: &[PathBuf]) -> Option<PathBuf> {
+        config_dirs
+            .iter()
+            .max_by(|a, b| self.version_suffix(a).cmp(&self.version_suffix(b)))
+            .cloned()
+    }
+
+    /// The version part of a config dir name, e.g. "2025.2" for `PyCharm2025.2`
+    fn version_suffix(&self, config_dir: &Path) -> Option<String> {
+        let name = config_dir.file_name()?.to_str()?;
+        self.product_codes.iter().find_map(|code| {
+            name.strip_prefix(code)
+                .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+                .map(str::to_string)
+        })
+    }
+
+    fn owns_data_dir(&self, name: &str) -> bool {
+        self.product_codes.iter().any(|code| {
+            name.strip_prefix(code)
+                .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        })
+    }
+
+    /// Installations of this IDE, from JetBrains Toolbox first and then the usual install
+    /// locations
+    fn installations(&self) -> Vec<Installation> {
+        let from_toolbox = toolbox::installed_tools().iter().filter_map(|tool| {
+            let info = ProductInfo::in_root(&tool.install_location)?;
+            let launcher = tool
+                .script
+                .clone()
+                .or_else(|| {
+                    tool.launch_command
+                        .as_ref()
+                        .map(|cmd| tool.install_location.join(cmd))
+                        .filter(|path| path.exists())
+                })
+                .or_else(|| info.launcher());
+            Some(Installation {
+                info,
+                launcher,
+                eap: tool.eap,
+            })
+        });
+        let from_cli = self.cli_candidates().into_iter().filter_map(|cli| {
+            let info = ProductInfo::for_cli(&cli)?;
+            Some(Installation {
+                info,
+                launcher: Some(cli),
+                eap: false,
+            })
+        });
+        let from_roots = Self::install_roots().into_iter().filter_map(|root| {
+            let info = ProductInfo::in_root(&root)?;
+            Some(Installation {
+                launcher: info.launcher(),
+                info,
+                eap: false,
+            })
+        });
+
+        let mut found: Vec<Installation> = Vec::new();
+        for install in from_toolbox.chain(from_cli).chain(from_roots) {
+            let dir = std::fs::canonicalize(&install.info.dir).unwrap_or_default();
+            if self.owns_data_dir(&install.info.data_directory_name)
+                && !found
+                    .iter()
+                    .any(|f| std::fs::canonicalize(&f.info.dir).unwrap_or_default() == dir)
+            {
+                found.push(install);
+            }
+        }
+
+        found
+    }
+
+    /// Directories that may hold a JetBrains installation
+    fn install_roots() -> Vec<PathBuf> {
+        let mut parents = Vec::new();
+
+        #[cfg(target_os = "macos")]
+        {
+            parents.push(PathBuf::from("/Applications"));
+            if let Some(home) = dirs::home_dir() {
+                parents.push(home.join("Applications"));
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            parents.push(PathBuf::from("/opt"));
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            if let Ok(programfiles) = std::env::var("ProgramFiles") {
+                parents.push(PathBuf::from(programfiles).join("JetBrains"));
+            }
+        }
+
+        parents
+            .into_iter()
+            .filter_map(|p| std::fs::read_dir(p).ok())
+            .flat_map(|entries| entries.flatten().map(|e| e.path()))
+            .chain(toolbox::app_install_dirs())
+            .chain(launchers::app_dir_entries())
+            .collect()
+    }
+
+    /// Config dirs of every version, under the sandbox's config dir if given
+    fn config_dirs(&self, sandbox: Option<&Sandbox>) -> Vec<PathBuf> {
         let mut dirs = Vec::new();
 
-        let base_path = {
+        let base_path = if let Some(sandbox) = sandbox {
+            sandbox.config_dir().map(|c| c.join(&self.vendor))
+        } else {
             #[cfg(target_os = "macos")]
             {
-                dirs::home_dir().map(|h| h.join("Library/Application Support/JetBrains"))
+                dirs::home_dir().map(|h| h.join("Library/Application Support").join(&self.vendor))
             }
             #[cfg(target_os = "linux")]
             {
-                dirs::home_dir().map(|h| h.join(".config/JetBrains"))
+                dirs::home_dir().map(|h| h.join(".config").join(&self.vendor))
             }
             #[cfg(target_os = "windows")]
             {
                 std::env::var("APPDATA")
                     .ok()
-                    .map(|p| PathBuf::from(p).join("JetBrains"))
+                    .map(|p| PathBuf::from(p).join(&self.vendor))
             }
         };
 
@@ -43,7 +330,7 @@ impl JetBrainsFamily {
             for entry in entries.flatten() {
                 let path = entry.path();
                 let name = path.file_name().unwrap_or_default().to_string_lossy();
-                if self.product_codes.iter().any(|code| name.starts_with(code)) {
+                if self.owns_data_dir(&name) {
                     dirs.push(path);
                 }
             }
@@ -51,15 +338,34 @@ impl JetBrainsFamily {
         dirs
     }
 
-    fn get_fallback_paths(&self) -> Vec<PathBuf> {
+    /// Where the IDE loads plugins from for a given config dir. Linux keeps them under
+    /// `~/.local/share/JetBrains` (or the sandbox's equivalent) rather than next to the config.
+    fn plugins_dir(&self, config_dir: &Path, sandbox: Option<&Sandbox>) -> Option<PathBuf> {
+        let name = config_dir.file_name()?;
+        if let Some(sandbox) = sandbox {
+            return sandbox.data_dir().map(|d| d.join(&self.vendor).join(name));
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            dirs::data_dir().map(|d| d.join(&self.vendor).join(name))
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            Some(config_dir.join("plugins"))
+        }
+    }
+
+    fn get_fallback_paths(&self, cli_command: &str) -> Vec<PathBuf> {
         let mut paths = Vec::new();
 
         #[cfg(target_os = "macos")]
         {
-            for app_name in self.macos_app_names {
+            for app_name in &self.macos_app_names {
                 let suffix = format!(
                     "Applications/{}.app/Contents/MacOS/{}",
-                    app_name, self.cli_command
+                    app_name, cli_command
                 );
                 paths.push(PathBuf::from(format!("/{suffix}")));
                 if let Some(home) = dirs::home_dir() {
@@ -68,39 +374,38 @@ impl JetBrainsFamily {
             }
         }
 
+        #[cfg(not(target_os = "windows"))]
+        {
+            for dir in nix::profile_bin_dirs() {
+                paths.push(dir.join(cli_command));
+            }
+        }
+
         #[cfg(target_os = "linux")]
         {
             paths.push(PathBuf::from(format!(
                 "/opt/{}/bin/{}",
-                self.cli_command, self.cli_command
-            )));
-            paths.push(PathBuf::from(format!(
-                "/usr/local/bin/{}",
-                self.cli_command
+                cli_command, cli_command
             )));
-            paths.push(PathBuf::from(format!("/snap/bin/{}", self.cli_command)));
+            paths.push(PathBuf::from(format!("/usr/local/bin/{}", cli_command)));
+            paths.push(PathBuf::from(format!("/snap/bin/{}", cli_command)));
 
-            if let Some(home) = dirs::home_dir() {
-                paths.push(home.join(format!(
-                    ".local/share/JetBrains/Toolbox/apps/{}/bin/{}",
-                    self.cli_command, self.cli_command
-                )));
+            if let Some(scripts) = toolbox::scripts_dir() {
+                paths.push(scripts.join(cli_command));
             }
+            paths.extend(launchers::launchers_named(cli_command));
         }
 
         #[cfg(target_os = "windows")]
         {
-            if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-                paths.push(PathBuf::from(format!(
-                    "{}/JetBrains/Toolbox/apps/{}/bin/{}.cmd",
-                    localappdata, self.cli_command, self.cli_command
-                )));
+            if let Some(scripts) = toolbox::scripts_dir() {
+                paths.push(scripts.join(format!("{}.cmd", cli_command)));
             }
             if let Ok(programfiles) = std::env::var("ProgramFiles") {
-                for app_name in self.macos_app_names {
+                for app_name in &self.macos_app_names {
                     paths.push(PathBuf::from(format!(
                         "{}/JetBrains/{}/bin/{}.bat",
-                        programfiles, app_name, self.cli_command
+                        programfiles, app_name, cli_command
                     )));
                 }
             }
@@ -110,59 +415,98 @@ impl JetBrainsFamily {
     }
 
     fn find_cli(&self) -> Option<PathBuf> {
-        if let Ok(path) = which(self.cli_command) {
-            return Some(path);
-        }
-        self.get_fallback_paths()
-            .into_iter()
-            .find(|path| path.exists())
+        self.cli_candidates().into_iter().next()
+    }
+
+    /// Every launcher that exists, PATH first
+    fn cli_candidates(&self) -> Vec<PathBuf> {
+        let on_path = self.cli_commands.iter().filter_map(|cmd| which(cmd).ok());
+        let fallbacks = self
+            .cli_commands
+            .iter()
+            .flat_map(|cmd| self.get_fallback_paths(cmd));
+        on_path
+            .chain(fallbacks)
+            .filter(|path| path.exists())
+            .collect()
     }
 
     fn is_running(&self) -> bool {
-        is_process_running(self.cli_command)
+        self.cli_commands.iter().any(|cmd| is_process_running(cmd))
     }
 }
 
-impl EditorPlugin for JetBrainsFamily {
-    fn name(&self) -> String {
-        self.name.to_string()
-    }
+/// A single installed version of a JetBrains IDE
+pub struct JetBrainsTarget {
+    family: JetBrainsFamily,
+    /// Flatpak or Snap the IDE runs in, `None` for a host install
+    sandbox: Option<Sandbox>,
+    version: Option<String>,
+    /// Config dir of this version, absent if the IDE hasn't been started yet
+    config_dir: Option<PathBuf>,
+    launcher: Option<PathBuf>,
+    product_info: Option<ProductInfo>,
+    /// Local plugin zip to unpack instead of downloading one
+    plugin_zip: Option<PathBuf>,
+    /// Skip the IDE's `installPlugins` CLI and unpack the plugin ourselves
+    unpack: bool,
+    nix_snippet_dir: Option<PathBuf>,
+}
 
-    fn is_installed(&self) -> bool {
-        !self.config_dirs().is_empty() || self.find_cli().is_some()
+impl JetBrainsTarget {
+    /// The nixpkgs attribute of the IDE if its launcher lives in the Nix store, where plugins
+    /// have to be added declaratively
+    fn nix_package(&self) -> Option<String> {
+        let store_package = nix::store_package(self.launcher.as_deref()?)?;
+        let pname = nix::pname(&store_package);
+        Some(if self.family.vendor == "JetBrains" {
+            format!("jetbrains.{pname}")
+        } else {
+            pname.to_string()
+        })
     }
 
-    fn install(&self) -> Result<()> {
-        if self.is_running() {
-            eprintln!(
-                "{}",
-                format!(
-                    "Warning: {} appears to be running. Please close it for the plugin to install correctly.",
-                    self.name
-                ).yellow()
-            );
+    /// The IDE launcher, run inside the sandbox for Flatpak and Snap installs
+    fn launcher_command(&self) -> Result<Command> {
+        if let Some(sandbox) = &self.sandbox {
+            return Ok(sandbox.command(None));
         }
 
         let cli_path = self
-            .find_cli()
-            .ok_or_else(|| eyre!("{} CLI not found", self.name))?;
-
-        let mut cmd;
+            .launcher
+            .as_deref()
+            .ok_or_else(|| eyre!("{} CLI not found", self.name()))?;
 
         #[cfg(target_os = "windows")]
         {
-            cmd = Command::new("cmd");
+            let mut cmd = Command::new("cmd");
             cmd.arg("/C");
-            cmd.arg(&cli_path);
+            cmd.arg(cli_path);
+            Ok(cmd)
         }
 
         #[cfg(not(target_os = "windows"))]
         {
-            cmd = Command::new(&cli_path);
+            Ok(Command::new(cli_path))
         }
+    }
 
-        let status = cmd
-            .args(["installPlugins", "com.wakatime.intellij.plugin"])
+    /// Where this version's copy of the plugin lives once installed
+    fn plugin_dir(&self) -> Option<PathBuf> {
+        let config_dir = self.config_dir.as_ref()?;
+        let plugins_dir = self.family.plugins_dir(config_dir, self.sandbox.as_ref())?;
+        Some(plugins_dir.join(jetbrains_zip::PLUGIN_DIR))
+    }
+
+    fn install_with_cli(&self) -> Result<()> {
+        // The IDE has no command to remove a plugin, so journal the folder it unpacks into
+        if let Some(plugin_dir) = self.plugin_dir() {
+            journal::record_write(&plugin_dir)?;
+        }
+
+        let status = self
+            .launcher_command()?
+            .args(["installPlugins", jetbrains_zip::PLUGIN_ID])
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
             .status()?;
@@ -170,7 +514,271 @@ impl EditorPlugin for JetBrainsFamily {
         if status.success() {
             Ok(())
         } else {
-            Err(eyre!("Failed to install WakaTime plugin for {}", self.name))
+            Err(eyre!(
+                "Failed to install WakaTime plugin for {}",
+                self.name()
+            ))
+        }
+    }
+
+    /// Unpacks the plugin zip straight into this version's plugins folder, which works
+    /// without a JVM and with the IDE open
+    fn install_by_unpacking(&self) -> Result<()> {
+        let config_dir = self.config_dir.as_ref().ok_or_else(|| {
+            eyre!(
+                "No {} config directory found. Start the IDE once, then try again.",
+                self.name()
+            )
+        })?;
+
+        let zip_path = match &self.plugin_zip {
+            Some(path) => path.clone(),
+            None => jetbrains_zip::download(
+                self.product_info
+                    .as_ref()
+                    .map(ProductInfo::marketplace_build)
+                    .as_deref(),
+            )?,
+        };
+
+        if let Some(info) = &self.product_info {
+            let range = jetbrains_zip::build_range(&zip_path)?;
+            if !range.contains(&info.build_number) {
+                return Err(eyre!(
+                    "{} supports IDE builds {}, but {} is build {}",
+                    zip_path.display(),
+                    range.describe(),
+                    self.name(),
+                    info.build_number
+                ));
+            }
+        }
+
+        let plugins_dir = self
+            .family
+            .plugins_dir(config_dir, self.sandbox.as_ref())
+            .ok_or_else(|| {
+                eyre!(
+                    "Could not determine plugins dir for {}",
+                    config_dir.display()
+                )
+            })?;
+        jetbrains_zip::unpack(&zip_path, &plugins_dir)
+    }
+}
+
+impl EditorPlugin for JetBrainsTarget {
+    fn name(&self) -> String {
+        let name = match &self.version {
+            Some(version) => format!("{} {}", self.family.name, version),
+            None => self.family.name.clone(),
+        };
+        match &self.sandbox {
+            Some(sandbox) => format!("{} ({})", name, sandbox.kind()),
+            None => name,
+        }
+    }
+
+    fn is_installed(&self) -> bool {
+        self.config_dir.is_some() || self.launcher.is_some() || self.sandbox.is_some()
+    }
+
+    fn install_path(&self) -> Option<PathBuf> {
+        self.plugin_dir()
+    }
+
+    fn plugin_version(&self) -> Option<String> {
+        jetbrains_zip::installed_version(&self.plugin_dir()?)
+    }
+
+    fn install(&self) -> Result<()> {
+        if let Some(package) = self.nix_package() {
+            let module = format!(
+                "  home.packages = [\n    (pkgs.jetbrains.plugins.addPlugins pkgs.{package} [ \"wakatime\" ])\n  ];"
+            );
+            return nix::emit_module(&self.name(), &module, self.nix_snippet_dir.as_deref());
         }
+
+        if self.unpack || self.plugin_zip.is_some() {
+            return self.install_by_unpacking();
+        }
+
+        if self.family.is_running() {
+            eprintln!(
+                "{}",
+                format!(
+                    "Warning: {} appears to be running. Please close it for the plugin to install correctly.",
+                    self.family.name
+                ).yellow()
+            );
+        }
+
+        let cli_result = self.install_with_cli();
+
+        // The CLI needs a working JVM and marketplace access; unpacking needs neither
+        cli_result.or_else(|e| {
+            self.install_by_unpacking()
+                .map_err(|unpack_err| eyre!("{} ({})", e, unpack_err))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::editor_plugins::manifest::Manifest;
+
+    fn pycharm() -> JetBrainsFamily {
+        serde_json::from_str(
+            r#"{
+                "name": "PyCharm",
+                "product_codes": ["PyCharm", "PyCharmCE"],
+                "cli_commands": ["hackatime-test-missing-pycharm"]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn write_product_info(dir: &Path, build: &str, data_dir: &str) {
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join("bin/pycharm.sh"), "").unwrap();
+        fs::write(
+            dir.join("product-info.json"),
+            format!(
+                r#"{{
+                    "name": "PyCharm",
+                    "version": "2025.2",
+                    "buildNumber": "{build}",
+                    "productCode": "PY",
+                    "dataDirectoryName": "{data_dir}",
+                    "launch": [
+                        {{"os": "Linux", "launcherPath": "bin/pycharm.sh"}},
+                        {{"os": "macOS", "launcherPath": "bin/pycharm.sh"}},
+                        {{"os": "Windows", "launcherPath": "bin/pycharm.sh"}}
+                    ]
+                }}"#
+            ),
+        )
+        .unwrap();
+    }
+
+    fn target(version: Option<&str>, config_dir: Option<PathBuf>) -> JetBrainsTarget {
+        JetBrainsTarget {
+            family: pycharm(),
+            sandbox: None,
+            version: version.map(str::to_string),
+            config_dir,
+            launcher: None,
+            product_info: None,
+            plugin_zip: None,
+            unpack: false,
+            nix_snippet_dir: None,
+        }
+    }
+
+    #[test]
+    fn reads_product_info_from_install_root() {
+        let root = tempfile::tempdir().unwrap();
+        write_product_info(root.path(), "252.23892.515", "PyCharm2025.2");
+
+        let info = ProductInfo::in_root(root.path()).unwrap();
+        assert_eq!(info.data_directory_name, "PyCharm2025.2");
+        assert_eq!(info.marketplace_build(), "PY-252.23892.515");
+        assert_eq!(info.launcher(), Some(root.path().join("bin/pycharm.sh")));
+
+        let from_cli = ProductInfo::for_cli(&root.path().join("bin/pycharm.sh")).unwrap();
+        assert_eq!(from_cli.build_number, "252.23892.515");
+    }
+
+    #[test]
+    fn reads_product_info_from_app_bundle() {
+        let app = tempfile::tempdir().unwrap();
+        write_product_info(
+            &app.path().join("Contents/Resources"),
+            "251.1",
+            "PyCharm2025.1",
+        );
+
+        let info = ProductInfo::in_root(app.path()).unwrap();
+        assert_eq!(info.version, "2025.2");
+        assert!(ProductInfo::in_root(&app.path().join("Contents")).is_none());
+    }
+
+    #[test]
+    fn matches_config_dirs_by_product_code() {
+        let family = pycharm();
+        assert!(family.owns_data_dir("PyCharm2025.2"));
+        assert!(family.owns_data_dir("PyCharmCE2024.3"));
+        assert!(!family.owns_data_dir("PyCharm"));
+        assert!(!family.owns_data_dir("PyCharmLogs"));
+        assert!(!family.owns_data_dir("IntelliJIdea2025.2"));
+
+        assert_eq!(
+            family.version_suffix(Path::new("/config/PyCharmCE2024.3")),
+            Some("2024.3".to_string())
+        );
+        assert_eq!(family.version_suffix(Path::new("/config/PyCharm")), None);
+    }
+
+    #[test]
+    fn picks_newest_config_dir() {
+        let family = pycharm();
+        let dirs = [
+            PathBuf::from("/config/PyCharm2024.3"),
+            PathBuf::from("/config/PyCharm2025.2"),
+            PathBuf::from("/config/PyCharm2025.1"),
+        ];
+        assert_eq!(family.newest_config_dir(&dirs), Some(dirs[1].clone()));
+        assert_eq!(family.newest_config_dir(&[]), None);
+    }
+
+    #[test]
+    fn bundled_families_own_only_their_config_dirs() {
+        let families = Manifest::bundled().jetbrains;
+        for family in &families {
+            assert!(
+                !family.cli_commands.is_empty(),
+                "{} has no CLI",
+                family.name
+            );
+            for code in &family.product_codes {
+                let dir = format!("{code}2025.2");
+                let owners: Vec<_> = families
+                    .iter()
+                    .filter(|f| f.vendor == family.vendor && f.owns_data_dir(&dir))
+                    .map(|f| f.name.as_str())
+                    .collect();
+                assert_eq!(owners, [family.name.as_str()], "{dir} is claimed twice");
+            }
+        }
+
+        let android_studio = families
+            .iter()
+            .find(|f| f.name == "Android Studio")
+            .unwrap();
+        assert_eq!(android_studio.vendor, "Google");
+        assert!(android_studio.owns_data_dir("AndroidStudio2025.1"));
+        assert!(!android_studio.owns_data_dir("AndroidStudioPreview2025.2"));
+    }
+
+    #[test]
+    fn names_targets_by_version() {
+        assert_eq!(
+            target(Some("2025.2 EAP"), None).name(),
+            "PyCharm 2025.2 EAP"
+        );
+        assert_eq!(target(None, None).name(), "PyCharm");
+    }
+
+    #[test]
+    fn installed_with_config_dir_or_launcher() {
+        assert!(!target(None, None).is_installed());
+        assert!(target(None, Some(PathBuf::from("/config/PyCharm2025.2"))).is_installed());
+
+        let mut with_launcher = target(None, None);
+        with_launcher.launcher = Some(PathBuf::from("/opt/pycharm/bin/pycharm.sh"));
+        assert!(with_launcher.is_installed());
     }
 }
@@ -0,0 +1,326 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use color_eyre::{Result, eyre::eyre};
+use zip::ZipArchive;
+
+use super::utils::cache_dir;
+use crate::{api, journal};
+
+pub const PLUGIN_ID: &str = "com.wakatime.intellij.plugin";
+
+/// The folder the plugin lives in inside an IDE's plugins dir
+pub const PLUGIN_DIR: &str = "WakaTime";
+
+const DOWNLOAD_URL: &str = "https://plugins.jetbrains.com/pluginManager";
+/// Time allowed for downloading the plugin zip, which can take a while on slow connections
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The `since-build`/`until-build` range from a plugin's `META-INF/plugin.xml`
+pub struct BuildRange {
+    since: Option<String>,
+    until: Option<String>,
+}
+
+impl BuildRange {
+    pub fn contains(&self, build: &str) -> bool {
+        let after_since = self
+            .since
+            .as_deref()
+            .is_none_or(|since| compare_builds(build, since) != Ordering::Less);
+        let before_until = self
+            .until
+            .as_deref()
+            .is_none_or(|until| compare_builds(build, until) != Ordering::Greater);
+        after_since && before_until
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} - {}",
+            self.since.as_deref().unwrap_or("any"),
+            self.until.as_deref().unwrap_or("any")
+        )
+    }
+}
+
+/// Compares dotted build numbers like `242.23339.11`. A `*` component, or a component
+/// missing from a range bound, matches anything.
+fn compare_builds(build: &str, bound: &str) -> Ordering {
+    for (b, r) in build.split('.').zip(bound.split('.')) {
+        if r == "*" {
+            return Ordering::Equal;
+        }
+        let ordering = match (b.parse::<u64>(), r.parse::<u64>()) {
+            (Ok(b), Ok(r)) => b.cmp(&r),
+            _ => b.cmp(r),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Downloads the newest plugin compatible with `build` (e.g. `IU-242.23339.11`), reusing an
+/// earlier download for the same build
+pub fn download(build: Option<&str>) -> Result<PathBuf> {
+    let cache_dir = cache_dir()
+        .ok_or_else(|| eyre!("Could not determine cache directory"))?
+        .join("jetbrains");
+    let zip_path = cache_dir.join(format!("{}-{}.zip", PLUGIN_ID, build.unwrap_or("latest")));
+    if zip_path.exists() {
+        return Ok(zip_path);
+    }
+
+    let mut query = vec![("action", "download"), ("id", PLUGIN_ID)];
+    if let Some(build) = build {
+        query.push(("build", build));
+    }
+
+    let bytes = api::client_builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()?
+        .get(DOWNLOAD_URL)
+        .query(&query)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.bytes())
+        .map_err(|e| eyre!("Failed to download the WakaTime plugin: {}", e))?;
+
+    fs::create_dir_all(&cache_dir)?;
+    let tmp_path = zip_path.with_extension("zip.part");
+    fs::write(&tmp_path, &bytes)
+        .map_err(|e| eyre!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, &zip_path)?;
+
+    Ok(zip_path)
+}
+
+fn open(zip_path: &Path) -> Result<ZipArchive<fs::File>> {
+    let file = fs::File::open(zip_path)
+        .map_err(|e| eyre!("Failed to open {}: {}", zip_path.display(), e))?;
+    ZipArchive::new(file).map_err(|e| eyre!("Invalid plugin zip {}: {}", zip_path.display(), e))
+}
+
+/// Reads the compatible build range from the `plugin.xml` inside the plugin's jars
+pub fn build_range(zip_path: &Path) -> Result<BuildRange> {
+    let mut archive = open(zip_path)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.name().ends_with(".jar") {
+            continue;
+        }
+
+        let mut jar = Vec::new();
+        entry.read_to_end(&mut jar)?;
+        let Some(xml) = plugin_xml(Cursor::new(jar)) else {
+            continue;
+        };
+
+        return Ok(BuildRange {
+            since: xml_attribute(&xml, "since-build"),
+            until: xml_attribute(&xml, "until-build"),
+        });
+    }
+
+    Err(eyre!("No plugin.xml found in {}", zip_path.display()))
+}
+
+/// The version of the plugin unpacked in `plugin_dir`, going by its jars' `plugin.xml`
+pub fn installed_version(plugin_dir: &Path) -> Option<String> {
+    fs::read_dir(plugin_dir.join("lib"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jar"))
+        .filter_map(|path| plugin_xml(fs::File::open(path).ok()?))
+        .find_map(|xml| xml_element(&xml, "version"))
+}
+
+/// `META-INF/plugin.xml` from a jar, if it has one
+fn plugin_xml(jar: impl Read + Seek) -> Option<String> {
+    let mut jar = ZipArchive::new(jar).ok()?;
+    let mut entry = jar.by_name("META-INF/plugin.xml").ok()?;
+    let mut xml = String::new();
+    entry.read_to_string(&mut xml).ok()?;
+    Some(xml)
+}
+
+/// The text of the first `<name>` element
+fn xml_element(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let len = xml[start..].find('<')?;
+    Some(xml[start..start + len].trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Pulls `name="value"` out of the `<idea-version>` element
+fn xml_attribute(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find("<idea-version")?;
+    let element = &xml[start..start + xml[start..].find('>')?];
+    let value_start = element.find(&format!("{name}=\""))? + name.len() + 2;
+    let value_len = element[value_start..].find('"')?;
+    Some(element[value_start..value_start + value_len].to_string()).filter(|v| !v.is_empty())
+}
+
+/// Unpacks the plugin into `plugins_dir`, replacing any previous copy so stale jars from an
+/// older version don't linger
+pub fn unpack(zip_path: &Path, plugins_dir: &Path) -> Result<()> {
+    let mut archive = open(zip_path)?;
+
+    let mut top_level: Vec<PathBuf> = archive
+        .file_names()
+        .filter_map(|name| match Path::new(name).components().next() {
+            Some(Component::Normal(dir)) => Some(plugins_dir.join(dir)),
+            _ => None,
+        })
+        .collect();
+    top_level.sort();
+    top_level.dedup();
+
+    for path in &top_level {
+        journal::record_write(path)?;
+    }
+    for dir in top_level.iter().filter(|d| d.is_dir()) {
+        fs::remove_dir_all(dir).map_err(|e| eyre!("Failed to remove {}: {}", dir.display(), e))?;
+    }
+
+    fs::create_dir_all(plugins_dir)?;
+    archive
+        .extract(plugins_dir)
+        .map_err(|e| eyre!("Failed to unpack into {}: {}", plugins_dir.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn plugin_jar(idea_version: &str) -> Vec<u8> {
+        let xml = format!(
+            "<idea-plugin>\n  <id>{PLUGIN_ID}</id>\n  <version>15.0.2</version>\n  {idea_version}\n</idea-plugin>"
+        );
+        zip(&[("META-INF/plugin.xml", xml.as_bytes())])
+    }
+
+    fn range(since: Option<&str>, until: Option<&str>) -> BuildRange {
+        BuildRange {
+            since: since.map(str::to_string),
+            until: until.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn compares_build_components_numerically() {
+        assert_eq!(compare_builds("241.1", "241.10"), Ordering::Less);
+        assert_eq!(compare_builds("241.10", "241.9"), Ordering::Greater);
+        assert_eq!(
+            compare_builds("242.23339.11", "242.23339.11"),
+            Ordering::Equal
+        );
+        assert_eq!(compare_builds("243.1", "242.*"), Ordering::Greater);
+        assert_eq!(compare_builds("242.99999", "242.*"), Ordering::Equal);
+        // A bound with fewer components matches any further ones
+        assert_eq!(compare_builds("242.23339.11", "242"), Ordering::Equal);
+    }
+
+    #[test]
+    fn build_range_contains_builds_between_bounds() {
+        let bounded = range(Some("241.1"), Some("242.*"));
+        assert!(bounded.contains("241.10"));
+        assert!(bounded.contains("242.23339.11"));
+        assert!(!bounded.contains("233.15026"));
+        assert!(!bounded.contains("243.1"));
+
+        let open = range(Some("241"), None);
+        assert!(open.contains("999.1"));
+        assert!(!open.contains("233.1"));
+        assert_eq!(open.describe(), "241 - any");
+    }
+
+    #[test]
+    fn reads_build_range_from_plugin_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("plugin.zip");
+        let jar = plugin_jar(r#"<idea-version since-build="241.1" until-build="242.*"/>"#);
+        fs::write(
+            &zip_path,
+            zip(&[
+                ("WakaTime/lib/other.jar", &zip(&[("README", b"none")])),
+                ("WakaTime/lib/WakaTime.jar", &jar),
+            ]),
+        )
+        .unwrap();
+
+        let range = build_range(&zip_path).unwrap();
+        assert_eq!(range.since.as_deref(), Some("241.1"));
+        assert_eq!(range.until.as_deref(), Some("242.*"));
+
+        let jar = plugin_jar(r#"<idea-version since-build="241.1" until-build=""/>"#);
+        fs::write(&zip_path, zip(&[("WakaTime/lib/WakaTime.jar", &jar)])).unwrap();
+        assert_eq!(build_range(&zip_path).unwrap().describe(), "241.1 - any");
+
+        fs::write(&zip_path, zip(&[("WakaTime/README", b"none")])).unwrap();
+        assert!(build_range(&zip_path).is_err());
+    }
+
+    #[test]
+    fn reads_installed_version_from_plugin_dir() {
+        let plugin_dir = tempfile::tempdir().unwrap();
+        assert_eq!(installed_version(plugin_dir.path()), None);
+
+        let lib = plugin_dir.path().join("lib");
+        fs::create_dir(&lib).unwrap();
+        fs::write(lib.join("kotlin-stdlib.jar"), zip(&[("README", b"none")])).unwrap();
+        fs::write(
+            lib.join("WakaTime.jar"),
+            plugin_jar(r#"<idea-version since-build="241"/>"#),
+        )
+        .unwrap();
+
+        assert_eq!(
+            installed_version(plugin_dir.path()),
+            Some("15.0.2".to_string())
+        );
+    }
+
+    #[test]
+    fn unpack_replaces_previous_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugins_dir = dir.path().join("plugins");
+        let stale = plugins_dir.join("WakaTime/lib/WakaTime-14.jar");
+        fs::create_dir_all(stale.parent().unwrap()).unwrap();
+        fs::write(&stale, "old").unwrap();
+        fs::create_dir(plugins_dir.join("Other")).unwrap();
+
+        let zip_path = dir.path().join("plugin.zip");
+        fs::write(&zip_path, zip(&[("WakaTime/lib/WakaTime.jar", b"new")])).unwrap();
+        unpack(&zip_path, &plugins_dir).unwrap();
+
+        assert!(!stale.exists());
+        assert_eq!(
+            fs::read_to_string(plugins_dir.join("WakaTime/lib/WakaTime.jar")).unwrap(),
+            "new"
+        );
+        assert!(plugins_dir.join("Other").is_dir());
+    }
+}
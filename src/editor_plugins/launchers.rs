@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where people unpack AppImages and tarballs by hand, relative to the home directory
+const APP_DIRS: &[&str] = &["Applications", "apps", "opt"];
+
+/// Launchers that aren't on PATH: AppImages and unpacked tarballs in the usual personal app
+/// dirs, and whatever `.desktop` entries point at
+pub fn launchers_named(cli_command: &str) -> Vec<PathBuf> {
+    all_launchers()
+        .iter()
+        .filter(|path| matches_command(path, cli_command))
+        .cloned()
+        .collect()
+}
+
+/// Top-level entries of the personal app dirs, which is where unpacked IDE tarballs live
+pub fn app_dir_entries() -> Vec<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new();
+    };
+    APP_DIRS
+        .iter()
+        .flat_map(|dir| read_dir(&home.join(dir)))
+        .collect()
+}
+
+fn all_launchers() -> &'static [PathBuf] {
+    static LAUNCHERS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+    LAUNCHERS.get_or_init(|| {
+        let mut launchers: Vec<PathBuf> = app_dir_entries()
+            .into_iter()
+            .flat_map(|entry| {
+                if entry.is_dir() {
+                    // <tarball>/<cli> or <tarball>/bin/<cli>
+                    let mut inner = read_dir(&entry);
+                    inner.extend(read_dir(&entry.join("bin")));
+                    inner
+                } else {
+                    vec![entry]
+                }
+            })
+            .chain(desktop_exec_targets())
+            .filter(|path| is_executable(path))
+            .collect();
+        launchers.sort();
+        launchers.dedup();
+        launchers
+    })
+}
+
+/// `cursor`, `cursor.sh`, `Cursor.AppImage` and versioned names like
+/// `Cursor-0.45.2-x86_64.AppImage` all count as the `cursor` launcher
+fn matches_command(path: &Path, cli_command: &str) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let name = name.to_ascii_lowercase();
+    let stem = name
+        .strip_suffix(".appimage")
+        .or_else(|| name.strip_suffix(".sh"))
+        .unwrap_or(&name);
+
+    match stem.strip_prefix(&cli_command.to_ascii_lowercase()) {
+        Some("") => true,
+        Some(rest) => rest
+            .strip_prefix(['-', '_'])
+            .is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit())),
+        None => false,
+    }
+}
+
+/// Absolute program paths from the `Exec=` lines of installed `.desktop` files
+fn desktop_exec_targets() -> Vec<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+    let mut dirs = vec![PathBuf::from("/usr/share/applications")];
+    if let Some(data) = dirs::data_dir() {
+        dirs.push(data.join("applications"));
+    }
+
+    dirs.iter()
+        .flat_map(|dir| read_dir(dir))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|content| {
+            content
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("Exec="))
+                .and_then(exec_program)
+        })
+        .collect()
+}
+
+/// The program an `Exec=` value runs, skipping an `env VAR=value` prefix. Relative names are
+/// resolved through PATH elsewhere, so only absolute paths are of interest.
+fn exec_program(exec: &str) -> Option<PathBuf> {
+    let mut args = exec_args(exec).into_iter();
+    let mut program = args.next()?;
+    if program == "env" || program.ends_with("/env") {
+        program = args.find(|arg| !arg.contains('='))?;
+    }
+    Some(PathBuf::from(program)).filter(|p| p.is_absolute())
+}
+
+/// Splits an `Exec=` value into arguments, honoring double quotes
+fn exec_args(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => current.extend(chars.next()),
+            ' ' if !quoted => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+fn read_dir(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .collect()
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_exec_args_with_quotes_and_field_codes() {
+        assert_eq!(
+            exec_args(r#"/opt/app/code --new-window %F"#),
+            ["/opt/app/code", "--new-window", "%F"]
+        );
+        assert_eq!(
+            exec_args(r#""/home/me/My Apps/Cursor.AppImage"  --no-sandbox %U"#),
+            ["/home/me/My Apps/Cursor.AppImage", "--no-sandbox", "%U"]
+        );
+        assert_eq!(
+            exec_args(r#""/opt/say \"hi\"/bin/app""#),
+            [r#"/opt/say "hi"/bin/app"#]
+        );
+        assert!(exec_args("  ").is_empty());
+    }
+
+    #[test]
+    fn finds_exec_program() {
+        assert_eq!(
+            exec_program("/opt/app/code %F"),
+            Some(PathBuf::from("/opt/app/code"))
+        );
+        assert_eq!(
+            exec_program("env FOO=1 /opt/app/code %F"),
+            Some(PathBuf::from("/opt/app/code"))
+        );
+        assert_eq!(
+            exec_program("/usr/bin/env FOO=1 BAR=2 /opt/app/code"),
+            Some(PathBuf::from("/opt/app/code"))
+        );
+        assert_eq!(
+            exec_program(r#""/home/me/My Apps/Cursor-0.45.2-x86_64.AppImage" %U"#),
+            Some(PathBuf::from(
+                "/home/me/My Apps/Cursor-0.45.2-x86_64.AppImage"
+            ))
+        );
+        // Relative names go through PATH instead
+        assert_eq!(exec_program("code %F"), None);
+        assert_eq!(exec_program("env FOO=1"), None);
+        assert_eq!(exec_program(""), None);
+    }
+
+    #[test]
+    fn matches_launcher_names() {
+        for name in [
+            "cursor",
+            "cursor.sh",
+            "Cursor.AppImage",
+            "Cursor-0.45.2-x86_64.AppImage",
+            "cursor_1.0.AppImage",
+        ] {
+            assert!(
+                matches_command(&Path::new("/opt").join(name), "cursor"),
+                "{name}"
+            );
+        }
+        for name in [
+            "cursor-nightly",
+            "cursorx",
+            "Cursor-x86_64.AppImage",
+            "code",
+        ] {
+            assert!(
+                !matches_command(&Path::new("/opt").join(name), "cursor"),
+                "{name}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_executable_files_are_launchers() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("code");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        assert!(!is_executable(&script));
+
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_executable(&script));
+        assert!(!is_executable(dir.path()));
+    }
+}
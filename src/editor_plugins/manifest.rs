@@ -0,0 +1,260 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use color_eyre::{Result, eyre::eyre};
+use colored::Colorize;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use super::utils::cache_dir;
+use super::{JetBrainsFamily, VsCodeFamily};
+use crate::api;
+
+/// Public keys a remote manifest from `--manifest-url` must be signed with, as printed by
+/// `cargo run --example sign_manifest -- keygen` (see `examples/sign_manifest.rs`). Empty until
+/// the maintainers generate a release key, so for now only the bundled manifest is used.
+const TRUSTED_KEYS: &[[u8; 32]] = &[];
+
+/// Registry of editor families, bundled so new IDEs only need a data change
+#[derive(Deserialize)]
+pub struct Manifest {
+    /// Bumped on every published change; a remote manifest is only used if it's newer
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default)]
+    pub vscode: Vec<VsCodeFamily>,
+    #[serde(default)]
+    pub jetbrains: Vec<JetBrainsFamily>,
+}
+
+impl Manifest {
+    pub fn bundled() -> Self {
+        serde_json::from_str(include_str!("editors.json"))
+            .expect("bundled editors.json should be valid")
+    }
+
+    /// The newest of the bundled and remote manifests, with `override_path` merged on top if
+    /// given
+    pub fn load(remote_url: Option<&str>, override_path: Option<&Path>) -> Result<Self> {
+        let mut manifest = Self::bundled();
+        if let Some(remote) = remote_url.and_then(fetch_remote)
+            && remote.version > manifest.version
+        {
+            manifest = remote;
+        }
+        if let Some(path) = override_path {
+            manifest.merge(Self::read(path)?);
+        }
+        Ok(manifest)
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| eyre!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content).map_err(|e| eyre!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Adds the families from `other`, replacing any with the same name
+    fn merge(&mut self, other: Self) {
+        merge_by_name(&mut self.vscode, other.vscode, |f| &f.name);
+        merge_by_name(&mut self.jetbrains, other.jetbrains, |f| &f.name);
+    }
+}
+
+fn merge_by_name<T>(base: &mut Vec<T>, overrides: Vec<T>, name: impl Fn(&T) -> &String) {
+    for family in overrides {
+        match base.iter_mut().find(|f| name(f) == name(&family)) {
+            Some(existing) => *existing = family,
+            None => base.push(family),
+        }
+    }
+}
+
+/// A manifest as published remotely: the manifest JSON and a base64 ed25519 signature over its
+/// exact bytes
+#[derive(Deserialize, Serialize)]
+struct SignedManifest {
+    manifest: String,
+    signature: String,
+}
+
+impl SignedManifest {
+    /// Verifies against each of `TRUSTED_KEYS`, returning the first match
+    fn verify(&self) -> Result<Manifest> {
+        let mut result = Err(eyre!("No trusted editor manifest key is configured"));
+        for key in TRUSTED_KEYS {
+            let key = VerifyingKey::from_bytes(key).expect("trusted manifest keys should be valid");
+            result = self.verify_with(&key);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    fn verify_with(&self, key: &VerifyingKey) -> Result<Manifest> {
+        let signature = BASE64_STANDARD
+            .decode(&self.signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| eyre!("Malformed editor manifest signature"))?;
+        key.verify_strict(self.manifest.as_bytes(), &signature)
+            .map_err(|_| eyre!("Editor manifest signature does not match"))?;
+
+        serde_json::from_str(&self.manifest).map_err(|e| eyre!("Invalid editor manifest: {}", e))
+    }
+}
+
+fn cached_manifest_path() -> Option<PathBuf> {
+    cache_dir().map(|d| d.join("editors.json"))
+}
+
+/// Fetches and verifies the manifest at `url`, caching it for offline runs. Falls back to the
+/// last verified copy if the fetch fails, and to nothing if that fails too.
+fn fetch_remote(url: &str) -> Option<Manifest> {
+    if TRUSTED_KEYS.is_empty() {
+        eprintln!(
+            "{}",
+            format!("Warning: ignoring {url}: this build has no trusted manifest key").yellow()
+        );
+        return None;
+    }
+
+    // Failing to download is expected offline, so only a bad signature is worth a warning
+    if let Ok(signed) = download(url) {
+        match signed.verify() {
+            Ok(manifest) => {
+                cache(&signed);
+                return Some(manifest);
+            }
+            Err(e) => eprintln!("{}", format!("Warning: ignoring {url}: {e}").yellow()),
+        }
+    }
+
+    let content = fs::read_to_string(cached_manifest_path()?).ok()?;
+    let signed: SignedManifest = serde_json::from_str(&content).ok()?;
+    signed.verify().ok()
+}
+
+fn download(url: &str) -> Result<SignedManifest> {
+    api::client_builder()
+        .timeout(Duration::from_secs(5))
+        .build()?
+        .get(url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.json())
+        .map_err(|e| eyre!("Failed to fetch editor manifest: {}", e))
+}
+
+fn cache(signed: &SignedManifest) {
+    let Some(path) = cached_manifest_path() else {
+        return;
+    };
+    let Ok(content) = serde_json::to_string(signed) else {
+        return;
+    };
+    let tmp_path = path.with_extension("json.part");
+    let _ = fs::create_dir_all(path.parent().unwrap_or(&path))
+        .and_then(|_| fs::write(&tmp_path, content))
+        .and_then(|_| fs::rename(&tmp_path, &path));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+
+    /// Signs like `examples/sign_manifest.rs` does
+    fn signed(key: &SigningKey, manifest: &str) -> SignedManifest {
+        SignedManifest {
+            manifest: manifest.to_string(),
+            signature: BASE64_STANDARD.encode(key.sign(manifest.as_bytes()).to_bytes()),
+        }
+    }
+
+    #[test]
+    fn bundled_manifest_parses() {
+        let manifest = Manifest::bundled();
+        assert!(manifest.version > 0);
+        assert!(!manifest.vscode.is_empty());
+        assert!(!manifest.jetbrains.is_empty());
+    }
+
+    #[test]
+    fn bundled_family_names_are_unique() {
+        let manifest = Manifest::bundled();
+        let mut names = HashSet::new();
+        for name in manifest.vscode.iter().map(|f| &f.name) {
+            assert!(names.insert(name), "duplicate VS Code family {name}");
+        }
+        let mut names = HashSet::new();
+        for name in manifest.jetbrains.iter().map(|f| &f.name) {
+            assert!(names.insert(name), "duplicate JetBrains family {name}");
+        }
+    }
+
+    #[test]
+    fn override_replaces_and_adds_families() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("editors.json");
+        fs::write(
+            &path,
+            r#"{
+                "vscode": [
+                    {"name": "Cursor", "config_subdirs": [".cursor-custom"], "cli_commands": ["cursor"]},
+                    {"name": "My Fork", "config_subdirs": [".my-fork"], "cli_commands": ["my-fork"]}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let bundled = Manifest::bundled();
+        let manifest = Manifest::load(None, Some(&path)).unwrap();
+        assert_eq!(manifest.vscode.len(), bundled.vscode.len() + 1);
+        assert_eq!(manifest.jetbrains.len(), bundled.jetbrains.len());
+        let cursor = manifest.vscode.iter().find(|f| f.name == "Cursor").unwrap();
+        assert_eq!(cursor.config_subdirs, [".cursor-custom"]);
+        assert_eq!(manifest.vscode.last().unwrap().name, "My Fork");
+
+        assert!(Manifest::load(None, Some(&dir.path().join("missing.json"))).is_err());
+    }
+
+    #[test]
+    fn signed_manifest_round_trips() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let manifest = signed(&key, r#"{"version": 2, "vscode": [], "jetbrains": []}"#)
+            .verify_with(&key.verifying_key())
+            .unwrap();
+        assert_eq!(manifest.version, 2);
+    }
+
+    #[test]
+    fn tampered_manifest_is_rejected() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut signed = signed(&key, r#"{"version": 2}"#);
+        signed.manifest = r#"{"version": 3}"#.to_string();
+        assert!(signed.verify_with(&key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn manifest_signed_with_another_key_is_rejected() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let signed = signed(&other, r#"{"version": 2}"#);
+        assert!(signed.verify_with(&key.verifying_key()).is_err());
+        assert!(signed.verify().is_err());
+    }
+
+    #[test]
+    fn trusted_keys_are_valid() {
+        for key in TRUSTED_KEYS {
+            assert!(VerifyingKey::from_bytes(key).is_ok());
+        }
+    }
+}
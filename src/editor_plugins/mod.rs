@@ -1,15 +1,29 @@
 mod jetbrains;
+mod jetbrains_zip;
+mod launchers;
+mod manifest;
+mod nix;
+mod sandbox;
+mod terminal;
+mod toolbox;
 mod utils;
 mod vscode;
+mod vscode_server;
 mod xcode;
 mod zed;
 
-use color_eyre::Result;
+use std::path::PathBuf;
+
+use color_eyre::{Result, eyre::eyre};
+use rayon::prelude::*;
 
 pub use jetbrains::JetBrainsFamily;
-pub use vscode::VsCodeFamily;
+use manifest::Manifest;
+pub use terminal::Terminal;
+pub use vscode::{VsCodeFamily, VsCodeSettings};
+pub use vscode_server::VsCodeServer;
 pub use xcode::Xcode;
-pub use zed::Zed;
+pub use zed::{Zed, ZedSettings};
 
 pub trait EditorPlugin: Send + Sync {
     /// Human-readable name, e.g. "VS Code", "Cursor"
@@ -20,129 +34,104 @@ pub trait EditorPlugin: Send + Sync {
 
     /// Install the WakaTime plugin for this editor
     fn install(&self) -> Result<()>;
+
+    /// Where `install` put the plugin or its configuration, for the state file
+    fn install_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Version of the installed plugin, if it can be told
+    fn plugin_version(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether setup offers this editor pre-selected
+    fn selected_by_default(&self) -> bool {
+        true
+    }
+
+    /// Remove whatever `install` set up for this editor
+    fn uninstall(&self) -> Result<()> {
+        Err(eyre!("Uninstalling is not supported for {}", self.name()))
+    }
+}
+
+pub const DEFAULT_VSIX_GALLERY_URL: &str = "https://open-vsx.org";
+
+/// Command-line settings that change how editors are detected and installed
+pub struct SetupOptions {
+    /// `--user-data-dir` for VS Code-family editors with a portable or custom layout
+    pub vscode_user_data_dir: Option<PathBuf>,
+    /// `--extensions-dir` for VS Code-family editors with a portable or custom layout
+    pub vscode_extensions_dir: Option<PathBuf>,
+    /// Name of the family the custom dirs above belong to, matched case-insensitively
+    pub vscode_family: String,
+    /// Open VSX-compatible gallery to download the `.vsix` from when `--install-extension` fails
+    pub vsix_gallery_url: String,
+    /// Local `.vsix` to use instead of downloading one
+    pub vsix_path: Option<PathBuf>,
+    /// WakaTime keys to write into each VS Code-family `settings.json`
+    pub vscode_settings: VsCodeSettings,
+    /// WakaTime extension options to write into Zed's `settings.json`
+    pub zed_settings: ZedSettings,
+    /// Local JetBrains plugin zip to unpack instead of using the IDE's CLI
+    pub jetbrains_plugin_zip: Option<PathBuf>,
+    /// Unpack the JetBrains plugin into the plugins dirs instead of using the IDE's CLI
+    pub jetbrains_unpack: bool,
+    /// Extra editor families merged over the bundled `editors.json`, replacing any with the
+    /// same name
+    pub editors_manifest: Option<PathBuf>,
+    /// Where to fetch newer signed editor manifests from, `None` to only use the bundled one
+    pub manifest_url: Option<String>,
+    /// Where to write home-manager modules for Nix-managed editors, instead of printing them
+    pub nix_snippet_dir: Option<PathBuf>,
+}
+
+pub fn all_editors(options: &SetupOptions) -> Result<Vec<Box<dyn EditorPlugin>>> {
+    let manifest = Manifest::load(
+        options.manifest_url.as_deref(),
+        options.editors_manifest.as_deref(),
+    )?;
+
+    let custom_dirs =
+        options.vscode_user_data_dir.is_some() || options.vscode_extensions_dir.is_some();
+    let family_known = manifest
+        .vscode
+        .iter()
+        .any(|family| family.name.eq_ignore_ascii_case(&options.vscode_family));
+    if custom_dirs && !family_known {
+        return Err(eyre!(
+            "Unknown VS Code-family editor {}",
+            options.vscode_family
+        ));
+    }
+
+    let mut editors: Vec<Box<dyn EditorPlugin>> = manifest
+        .vscode
+        .into_par_iter()
+        .flat_map_iter(|family| family.targets(options))
+        .collect();
+    editors.extend(other_editors(options));
+    editors.extend(VsCodeServer::wsl().map(|server| -> Box<dyn EditorPlugin> { Box::new(server) }));
+    editors.par_extend(
+        manifest
+            .jetbrains
+            .into_par_iter()
+            .flat_map_iter(|family| family.targets(options)),
+    );
+    Ok(editors)
 }
 
-pub fn all_editors() -> Vec<Box<dyn EditorPlugin>> {
-    vec![
-        // VS Code family
-        Box::new(VsCodeFamily {
-            name: "VS Code",
-            config_subdir: ".vscode",
-            cli_command: "code",
-            macos_app_name: "Visual Studio Code",
-            windows_app_folder: "Microsoft VS Code",
-        }),
-        Box::new(VsCodeFamily {
-            name: "Cursor",
-            config_subdir: ".cursor",
-            cli_command: "cursor",
-            macos_app_name: "Cursor",
-            windows_app_folder: "cursor",
-        }),
-        Box::new(VsCodeFamily {
-            name: "Windsurf",
-            config_subdir: ".windsurf",
-            cli_command: "windsurf",
-            macos_app_name: "Windsurf",
-            windows_app_folder: "windsurf",
-        }),
-        Box::new(VsCodeFamily {
-            name: "Antigravity",
-            config_subdir: ".antigravity",
-            cli_command: "antigravity",
-            macos_app_name: "Antigravity",
-            windows_app_folder: "antigravity",
-        }),
-        Box::new(VsCodeFamily {
-            name: "VSCodium",
-            config_subdir: ".vscode-oss",
-            cli_command: "codium",
-            macos_app_name: "VSCodium",
-            windows_app_folder: "VSCodium",
-        }),
-        Box::new(VsCodeFamily {
-            name: "Trae",
-            config_subdir: ".trae",
-            cli_command: "trae",
-            macos_app_name: "Trae",
-            windows_app_folder: "Trae",
-        }),
-        // Xcode (macOS only)
-        Box::new(Xcode),
-        // Zed
-        Box::new(Zed),
-        // JetBrains family
-        Box::new(JetBrainsFamily {
-            name: "IntelliJ IDEA",
-            product_codes: &["IntelliJIdea", "IdeaIC"],
-            cli_command: "idea",
-            macos_app_names: &["IntelliJ IDEA", "IntelliJ IDEA CE"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "PyCharm",
-            product_codes: &["PyCharm", "PyCharmCE"],
-            cli_command: "pycharm",
-            macos_app_names: &["PyCharm", "PyCharm CE"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "WebStorm",
-            product_codes: &["WebStorm"],
-            cli_command: "webstorm",
-            macos_app_names: &["WebStorm"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "GoLand",
-            product_codes: &["GoLand"],
-            cli_command: "goland",
-            macos_app_names: &["GoLand"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "RustRover",
-            product_codes: &["RustRover"],
-            cli_command: "rustrover",
-            macos_app_names: &["RustRover"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "RubyMine",
-            product_codes: &["RubyMine"],
-            cli_command: "rubymine",
-            macos_app_names: &["RubyMine"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "PhpStorm",
-            product_codes: &["PhpStorm"],
-            cli_command: "phpstorm",
-            macos_app_names: &["PhpStorm"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "CLion",
-            product_codes: &["CLion"],
-            cli_command: "clion",
-            macos_app_names: &["CLion"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "DataGrip",
-            product_codes: &["DataGrip"],
-            cli_command: "datagrip",
-            macos_app_names: &["DataGrip"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "Rider",
-            product_codes: &["Rider"],
-            cli_command: "rider",
-            macos_app_names: &["Rider"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "Android Studio",
-            product_codes: &["AndroidStudio"],
-            cli_command: "studio",
-            macos_app_names: &["Android Studio"],
-        }),
-        Box::new(JetBrainsFamily {
-            name: "AppCode",
-            product_codes: &["AppCode"],
-            cli_command: "appcode",
-            macos_app_names: &["AppCode"],
-        }),
-    ]
+fn other_editors(options: &SetupOptions) -> Vec<Box<dyn EditorPlugin>> {
+    // Xcode (macOS only)
+    let mut editors: Vec<Box<dyn EditorPlugin>> = vec![Box::new(Xcode)];
+    // Zed, one per release channel
+    editors.extend(
+        Zed::channels(&options.zed_settings)
+            .into_iter()
+            .map(|zed| -> Box<dyn EditorPlugin> { Box::new(zed) }),
+    );
+    // Shell prompt hooks
+    editors.push(Box::new(Terminal));
+    editors
 }
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::{Result, eyre::eyre};
+
+use crate::journal;
+
+/// Bin dirs of Nix profiles: `nix profile`/`nix-env`, home-manager's per-user profile and
+/// NixOS system packages
+pub fn profile_bin_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".nix-profile/bin"));
+        dirs.push(home.join(".local/state/nix/profile/bin"));
+    }
+    if let Ok(user) = std::env::var("USER") {
+        dirs.push(PathBuf::from(format!("/etc/profiles/per-user/{user}/bin")));
+    }
+    dirs.push(PathBuf::from("/run/current-system/sw/bin"));
+    dirs
+}
+
+/// The store path name a launcher resolves into, e.g. `vscode-1.95.3` for
+/// `~/.nix-profile/bin/code`. `None` if it doesn't live in the Nix store.
+pub fn store_package(launcher: &Path) -> Option<String> {
+    let resolved = fs::canonicalize(launcher).ok()?;
+    let name = resolved.strip_prefix("/nix/store").ok()?.iter().next()?;
+    // <32-char hash>-<name>
+    let (_, name) = name.to_str()?.split_once('-')?;
+    Some(name.to_string())
+}
+
+/// The package name without its version, e.g. `pycharm-community` for
+/// `pycharm-community-2024.1.4`
+pub fn pname(store_package: &str) -> &str {
+    store_package
+        .match_indices('-')
+        .find(|(i, _)| store_package[i + 1..].starts_with(|c: char| c.is_ascii_digit()))
+        .map_or(store_package, |(i, _)| &store_package[..i])
+}
+
+fn module_path(dir: &Path, editor_name: &str) -> PathBuf {
+    let slug: String = editor_name
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    dir.join(format!("hackatime-{slug}.nix"))
+}
+
+/// Hands `body` (home-manager options) to the user as a module, since the store can't be
+/// changed in place. Writes it into `dir` if given, otherwise prints it for the user to add.
+pub fn emit_module(editor_name: &str, body: &str, dir: Option<&Path>) -> Result<()> {
+    let module = format!(
+        "# Generated by hackatime-setup for {editor_name}. Add this file to `imports`.\n{{ pkgs, ... }}:\n{{\n{}\n}}\n",
+        body.trim_end()
+    );
+
+    let Some(dir) = dir else {
+        println!(
+            "\n{} is managed by Nix. Add this to your home-manager configuration to finish setting it up:\n\n{}",
+            editor_name, module
+        );
+        return Ok(());
+    };
+
+    let path = module_path(dir, editor_name);
+    fs::create_dir_all(dir)?;
+    journal::record_write(&path)?;
+    fs::write(&path, module).map_err(|e| eyre!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Removes a module written by `emit_module`
+pub fn remove_module(editor_name: &str, dir: Option<&Path>) -> Result<()> {
+    let path = dir.map(|d| module_path(d, editor_name));
+    match path.filter(|p| p.exists()) {
+        Some(path) => {
+            fs::remove_file(&path).map_err(|e| eyre!("Failed to remove {}: {}", path.display(), e))
+        }
+        None => Err(eyre!(
+            "{} is managed by Nix. Remove WakaTime from your home-manager configuration instead.",
+            editor_name
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_version_from_package_name() {
+        assert_eq!(pname("pycharm-community-2024.1.4"), "pycharm-community");
+        assert_eq!(pname("vscode-1.95.3"), "vscode");
+        assert_eq!(pname("zed-editor-0.170.4"), "zed-editor");
+        assert_eq!(pname("vscodium"), "vscodium");
+    }
+
+    #[test]
+    fn launchers_outside_the_store_have_no_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let launcher = dir.path().join("code");
+        fs::write(&launcher, "").unwrap();
+        assert_eq!(store_package(&launcher), None);
+        assert_eq!(store_package(&dir.path().join("missing")), None);
+    }
+
+    #[test]
+    fn writes_and_removes_module() {
+        let dir = tempfile::tempdir().unwrap();
+        emit_module(
+            "VS Code (Insiders)",
+            "  programs.vscode.enable = true;\n",
+            Some(dir.path()),
+        )
+        .unwrap();
+
+        let path = dir.path().join("hackatime-vs-code--insiders-.nix");
+        let module = fs::read_to_string(&path).unwrap();
+        assert!(module.starts_with("# Generated by hackatime-setup for VS Code (Insiders)."));
+        assert!(module.ends_with("{ pkgs, ... }:\n{\n  programs.vscode.enable = true;\n}\n"));
+
+        remove_module("VS Code (Insiders)", Some(dir.path())).unwrap();
+        assert!(!path.exists());
+        assert!(remove_module("VS Code (Insiders)", Some(dir.path())).is_err());
+        assert!(remove_module("VS Code (Insiders)", None).is_err());
+    }
+}
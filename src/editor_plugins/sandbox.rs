@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use color_eyre::{Result, eyre::eyre};
+
+use crate::journal;
+
+/// A Linux package format that gives the app its own view of the home directory, so its config
+/// lives somewhere other than where the host install would keep it
+#[derive(Clone)]
+pub enum Sandbox {
+    /// Flatpak app id, e.g. `com.visualstudio.code`
+    Flatpak(String),
+    /// Strictly confined snap name. Classic snaps see the real home and need no special casing.
+    Snap(String),
+}
+
+impl Sandbox {
+    /// Sandboxes from `flatpak_ids` and `snap_names` that are installed
+    pub fn installed(flatpak_ids: &[String], snap_names: &[String]) -> Vec<Self> {
+        let flatpaks = flatpak_ids
+            .iter()
+            .filter(|id| flatpak_apps().contains(id))
+            .map(|id| Self::Flatpak(id.clone()));
+        let snaps = snap_names
+            .iter()
+            .filter(|name| is_strict_snap(name))
+            .map(|name| Self::Snap(name.clone()));
+        flatpaks.chain(snaps).collect()
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Flatpak(_) => "Flatpak",
+            Self::Snap(_) => "Snap",
+        }
+    }
+
+    /// Where the app's dotfiles end up: `~/.var/app/<id>` for Flatpak (via `--persist`) and
+    /// `~/snap/<name>/current` for Snap
+    pub fn home(&self) -> Option<PathBuf> {
+        let home = dirs::home_dir()?;
+        Some(match self {
+            Self::Flatpak(id) => home.join(".var/app").join(id),
+            Self::Snap(name) => home.join("snap").join(name).join("current"),
+        })
+    }
+
+    /// The app's `XDG_CONFIG_HOME`
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        let home = self.home()?;
+        Some(match self {
+            Self::Flatpak(_) => home.join("config"),
+            Self::Snap(_) => home.join(".config"),
+        })
+    }
+
+    /// The app's `XDG_DATA_HOME`
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        let home = self.home()?;
+        Some(match self {
+            Self::Flatpak(_) => home.join("data"),
+            Self::Snap(_) => home.join(".local/share"),
+        })
+    }
+
+    /// Where the app's WakaTime plugin reads its config from, if it can't see `~/.wakatime.cfg`.
+    /// Strict snaps get `$HOME` pointed at their own dir; Flatpak keeps the real one.
+    fn wakatime_config(&self) -> Option<PathBuf> {
+        match self {
+            Self::Flatpak(_) => None,
+            Self::Snap(_) => Some(self.home()?.join(".wakatime.cfg")),
+        }
+    }
+
+    /// Copies `~/.wakatime.cfg` to where the app looks for it
+    pub fn share_wakatime_config(&self) -> Result<()> {
+        let Some(target) = self.wakatime_config() else {
+            return Ok(());
+        };
+        let source = dirs::home_dir()
+            .ok_or_else(|| eyre!("Could not find home directory"))?
+            .join(".wakatime.cfg");
+
+        journal::record_write(&target)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&source, &target).map(|_| ()).map_err(|e| {
+            eyre!(
+                "Failed to copy {} to {}: {}",
+                source.display(),
+                target.display(),
+                e
+            )
+        })
+    }
+
+    pub fn unshare_wakatime_config(&self) -> Result<()> {
+        match self.wakatime_config().filter(|p| p.exists()) {
+            Some(path) => fs::remove_file(&path)
+                .map_err(|e| eyre!("Failed to remove {}: {}", path.display(), e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs `program` inside the sandbox, or the app's default command if `None`
+    pub fn command(&self, program: Option<&str>) -> Command {
+        match self {
+            Self::Flatpak(id) => {
+                let mut cmd = Command::new("flatpak");
+                cmd.arg("run");
+                if let Some(program) = program {
+                    cmd.arg(format!("--command={program}"));
+                }
+                cmd.arg(id);
+                cmd
+            }
+            Self::Snap(name) => match program {
+                Some(program) => Command::new(format!("/snap/bin/{program}")),
+                None => {
+                    let mut cmd = Command::new("snap");
+                    cmd.args(["run", name]);
+                    cmd
+                }
+            },
+        }
+    }
+}
+
+/// App ids from `flatpak list --app`, user and system installs alike. Read once and shared by
+/// all editors.
+fn flatpak_apps() -> &'static [String] {
+    static APPS: OnceLock<Vec<String>> = OnceLock::new();
+    APPS.get_or_init(|| {
+        if !cfg!(target_os = "linux") {
+            return Vec::new();
+        }
+        Command::new("flatpak")
+            .args(["list", "--app", "--columns=application"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| {
+                String::from_utf8_lossy(&o.stdout)
+                    .lines()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    })
+}
+
+fn is_strict_snap(name: &str) -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    fs::read_to_string(format!("/snap/{name}/current/meta/snap.yaml"))
+        .is_ok_and(|yaml| !is_classic(&yaml))
+}
+
+fn is_classic(snap_yaml: &str) -> bool {
+    snap_yaml
+        .lines()
+        .any(|l| l.trim() == "confinement: classic")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<&OsStr> {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .collect()
+    }
+
+    #[test]
+    fn flatpak_dirs_live_under_var_app() {
+        let sandbox = Sandbox::Flatpak("com.visualstudio.code".to_string());
+        let home = dirs::home_dir()
+            .unwrap()
+            .join(".var/app/com.visualstudio.code");
+        assert_eq!(sandbox.home(), Some(home.clone()));
+        assert_eq!(sandbox.config_dir(), Some(home.join("config")));
+        assert_eq!(sandbox.data_dir(), Some(home.join("data")));
+        assert_eq!(sandbox.wakatime_config(), None);
+        assert_eq!(sandbox.kind(), "Flatpak");
+    }
+
+    #[test]
+    fn snap_dirs_live_under_snap_current() {
+        let sandbox = Sandbox::Snap("codium".to_string());
+        let home = dirs::home_dir().unwrap().join("snap/codium/current");
+        assert_eq!(sandbox.home(), Some(home.clone()));
+        assert_eq!(sandbox.config_dir(), Some(home.join(".config")));
+        assert_eq!(sandbox.data_dir(), Some(home.join(".local/share")));
+        assert_eq!(sandbox.wakatime_config(), Some(home.join(".wakatime.cfg")));
+        assert_eq!(sandbox.kind(), "Snap");
+    }
+
+    #[test]
+    fn runs_commands_inside_the_sandbox() {
+        let flatpak = Sandbox::Flatpak("com.vscodium.codium".to_string());
+        assert_eq!(
+            args(&flatpak.command(Some("codium"))),
+            ["flatpak", "run", "--command=codium", "com.vscodium.codium"]
+        );
+        assert_eq!(
+            args(&flatpak.command(None)),
+            ["flatpak", "run", "com.vscodium.codium"]
+        );
+
+        let snap = Sandbox::Snap("pycharm-community".to_string());
+        assert_eq!(args(&snap.command(Some("code"))), ["/snap/bin/code"]);
+        assert_eq!(
+            args(&snap.command(None)),
+            ["snap", "run", "pycharm-community"]
+        );
+    }
+
+    #[test]
+    fn tells_classic_snaps_apart() {
+        assert!(is_classic(
+            "name: code\nconfinement: classic\ngrade: stable\n"
+        ));
+        assert!(!is_classic("name: codium\nconfinement: strict\n"));
+        assert!(!is_classic("name: codium\n"));
+    }
+}
@@ -0,0 +1,282 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::{Result, eyre::eyre};
+use colored::Colorize;
+use which::which;
+
+use super::EditorPlugin;
+use super::utils::{remove_marked_block, upsert_marked_block};
+
+const PLUGIN_NAME: &str = concat!("hackatime-setup-terminal/", env!("CARGO_PKG_VERSION"));
+
+const BASH_HOOK: &str = r#"__hackatime_hook() {
+    local cli num entity rest
+    read -r num entity rest <<< "$(fc -l -0 2>/dev/null)"
+    # An empty command line leaves the history number as is, so only new entries count
+    if [[ -z "${__hackatime_last+x}" || "$num" == "$__hackatime_last" ]]; then
+        __hackatime_last="$num"
+        return
+    fi
+    __hackatime_last="$num"
+    cli="$(command -v wakatime-cli || echo "$HOME/.wakatime/wakatime-cli")"
+    [ -x "$cli" ] || return
+    [ -n "$entity" ] || return
+    ("$cli" --write --plugin "{PLUGIN}" --entity-type app --entity "$entity" --project "${PWD##*/}" >/dev/null 2>&1 &)
+}
+if [[ -n "${precmd_functions+x}" ]]; then
+    precmd_functions+=(__hackatime_hook)
+elif [[ ";${PROMPT_COMMAND:-};" != *";__hackatime_hook;"* ]]; then
+    PROMPT_COMMAND="__hackatime_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi"#;
+
+const ZSH_HOOK: &str = r#"__hackatime_hook() {
+    local cli num entity rest
+    read -r num entity rest <<< "$(fc -l -1 2>/dev/null)"
+    # An empty command line leaves the history number as is, so only new entries count
+    if [[ -z "${__hackatime_last+x}" || "$num" == "$__hackatime_last" ]]; then
+        __hackatime_last="$num"
+        return
+    fi
+    __hackatime_last="$num"
+    cli="${commands[wakatime-cli]:-$HOME/.wakatime/wakatime-cli}"
+    [[ -x "$cli" ]] || return
+    [[ -n "$entity" ]] || return
+    ("$cli" --write --plugin "{PLUGIN}" --entity-type app --entity "$entity" --project "${PWD:t}" >/dev/null 2>&1 &)
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd __hackatime_hook"#;
+
+const FISH_HOOK: &str = r#"function __hackatime_hook --on-event fish_preexec
+    set -l cli (command -v wakatime-cli; or echo $HOME/.wakatime/wakatime-cli)
+    test -x "$cli"; or return
+    set -l entity (string split -f1 ' ' -- (string trim -- $argv[1]))
+    test -n "$entity"; or return
+    $cli --write --plugin "{PLUGIN}" --entity-type app --entity $entity --project (basename $PWD) >/dev/null 2>&1 &
+    disown 2>/dev/null
+end"#;
+
+/// Names that only turn up in lines loading another WakaTime shell integration:
+/// bash-wakatime, zsh-wakatime, wakatime-zsh-plugin (zinit, antigen), terminal-wakatime and
+/// wakatime.fish
+const FOREIGN_HOOKS: &[&str] = &[
+    "bash-wakatime",
+    "zsh-wakatime",
+    "wakatime-zsh-plugin",
+    "terminal-wakatime",
+    "wakatime.fish",
+];
+
+/// The first line of `content` that loads another WakaTime shell integration, with its 1-based
+/// line number. Our own block never matches.
+fn foreign_hook(content: &str) -> Option<(usize, &str)> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .find(|(_, line)| {
+            let line = line.trim_start();
+            if line.starts_with('#') {
+                return false;
+            }
+            // oh-my-zsh's `plugins=(git wakatime)`
+            let omz_plugin = line.starts_with("plugins=(")
+                && line
+                    .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                    .any(|plugin| plugin == "wakatime");
+            omz_plugin || FOREIGN_HOOKS.iter().any(|hook| line.contains(hook))
+        })
+}
+
+#[derive(Clone, Copy)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    const ALL: [Shell; 3] = [Shell::Bash, Shell::Zsh, Shell::Fish];
+
+    fn binary(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    fn rc_file(self) -> Option<PathBuf> {
+        match self {
+            Shell::Bash => dirs::home_dir().map(|h| h.join(".bashrc")),
+            // prezto and similar frameworks move the zsh dotfiles via ZDOTDIR
+            Shell::Zsh => std::env::var("ZDOTDIR")
+                .map(PathBuf::from)
+                .ok()
+                .or_else(dirs::home_dir)
+                .map(|d| d.join(".zshrc")),
+            Shell::Fish => std::env::var("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .ok()
+                .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+                .map(|c| c.join("fish/config.fish")),
+        }
+    }
+
+    fn hook(self) -> String {
+        let hook = match self {
+            Shell::Bash => BASH_HOOK,
+            Shell::Zsh => ZSH_HOOK,
+            Shell::Fish => FISH_HOOK,
+        };
+        hook.replace("{PLUGIN}", PLUGIN_NAME)
+    }
+
+    fn is_installed(self) -> bool {
+        which(self.binary()).is_ok()
+    }
+}
+
+/// Tracks time spent in bash, zsh and fish through a prompt hook that sends app heartbeats.
+pub struct Terminal;
+
+impl Terminal {
+    fn installed_shells() -> Vec<Shell> {
+        Shell::ALL
+            .into_iter()
+            .filter(|s| s.is_installed())
+            .collect()
+    }
+
+    /// Where another WakaTime shell integration (e.g. oh-my-zsh's `wakatime` plugin) is
+    /// already set up in `rc_file`, as the line number and line
+    fn foreign_hook_in(rc_file: &Path) -> Option<(usize, String)> {
+        let content = fs::read_to_string(rc_file).ok()?;
+        foreign_hook(&content).map(|(number, line)| (number, line.trim().to_string()))
+    }
+}
+
+impl EditorPlugin for Terminal {
+    fn name(&self) -> String {
+        "Terminal".to_string()
+    }
+
+    fn is_installed(&self) -> bool {
+        cfg!(not(target_os = "windows")) && !Self::installed_shells().is_empty()
+    }
+
+    /// Every Unix box has bash, which says nothing about whether anyone works in it
+    fn selected_by_default(&self) -> bool {
+        false
+    }
+
+    fn install_path(&self) -> Option<PathBuf> {
+        Self::installed_shells()
+            .into_iter()
+            .find_map(Shell::rc_file)
+    }
+
+    fn install(&self) -> Result<()> {
+        let shells = Self::installed_shells();
+        if shells.is_empty() {
+            return Err(eyre!("No supported shell (bash, zsh, fish) found"));
+        }
+
+        for shell in shells {
+            let rc_file = shell
+                .rc_file()
+                .ok_or_else(|| eyre!("Could not determine {} config file", shell.binary()))?;
+
+            if let Some((number, line)) = Self::foreign_hook_in(&rc_file) {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "Note: skipping {}, {}:{} already sets up WakaTime: {}",
+                        shell.binary(),
+                        rc_file.display(),
+                        number,
+                        line
+                    )
+                    .dimmed()
+                );
+                continue;
+            }
+
+            upsert_marked_block(&rc_file, &shell.hook())?;
+        }
+
+        Ok(())
+    }
+
+    fn uninstall(&self) -> Result<()> {
+        for shell in Shell::ALL {
+            if let Some(rc_file) = shell.rc_file() {
+                remove_marked_block(&rc_file)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks_name_this_plugin() {
+        for shell in Shell::ALL {
+            let hook = shell.hook();
+            assert!(hook.contains(PLUGIN_NAME), "{}", shell.binary());
+            assert!(!hook.contains("{PLUGIN}"), "{}", shell.binary());
+        }
+    }
+
+    #[test]
+    fn upserts_and_removes_hook_per_shell() {
+        for shell in Shell::ALL {
+            let dir = tempfile::tempdir().unwrap();
+            let rc_file = dir.path().join("rc");
+            let original = "export EDITOR=vim\n";
+            fs::write(&rc_file, original).unwrap();
+
+            upsert_marked_block(&rc_file, &shell.hook()).unwrap();
+            // A second run replaces the block rather than adding another
+            upsert_marked_block(&rc_file, &shell.hook()).unwrap();
+            let content = fs::read_to_string(&rc_file).unwrap();
+            assert!(content.starts_with(original), "{}", shell.binary());
+            assert_eq!(
+                content.matches("__hackatime_hook").count(),
+                shell.hook().matches("__hackatime_hook").count()
+            );
+            assert!(foreign_hook(&content).is_none(), "{}", shell.binary());
+
+            assert!(remove_marked_block(&rc_file).unwrap());
+            assert_eq!(fs::read_to_string(&rc_file).unwrap(), original);
+        }
+    }
+
+    #[test]
+    fn finds_foreign_hooks() {
+        let cases = [
+            "source ~/.bash-wakatime/bash-wakatime.sh",
+            ". \"$HOME/.zsh-wakatime/zsh-wakatime.plugin.zsh\"",
+            "zinit light sobolevn/wakatime-zsh-plugin",
+            "plugins=(git wakatime docker)",
+            "eval \"$(terminal-wakatime init)\"",
+            "terminal-wakatime init fish | source",
+        ];
+        for hook in cases {
+            let content = format!("export EDITOR=vim\n{hook}\n");
+            assert_eq!(foreign_hook(&content), Some((2, hook)), "{hook}");
+        }
+    }
+
+    #[test]
+    fn ignores_other_wakatime_mentions() {
+        let content = "export WAKATIME_HOME=~/.config/wakatime\n\
+            alias wt=wakatime-cli\n\
+            # source ~/.bash-wakatime/bash-wakatime.sh\n\
+            plugins=(git wakatime-extra)\n";
+        assert_eq!(foreign_hook(content), None);
+    }
+}
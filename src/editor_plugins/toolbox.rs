@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+/// An IDE installed and managed by JetBrains Toolbox
+pub struct ToolboxTool {
+    pub install_location: PathBuf,
+    /// Launcher relative to `install_location`, e.g. `bin/pycharm.sh`
+    pub launch_command: Option<String>,
+    /// Shell script Toolbox generated for this tool, if script generation is enabled
+    pub script: Option<PathBuf>,
+    /// Whether this comes from an EAP channel rather than a release
+    pub eap: bool,
+}
+
+#[derive(Deserialize)]
+struct State {
+    #[serde(default)]
+    tools: Vec<StateTool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StateTool {
+    install_location: PathBuf,
+    launch_command: Option<String>,
+    #[serde(default)]
+    display_version: String,
+    #[serde(default)]
+    channel_id: String,
+    #[serde(default)]
+    tag: String,
+}
+
+#[derive(Deserialize)]
+struct Settings {
+    shell_scripts: Option<ShellScripts>,
+}
+
+#[derive(Deserialize)]
+struct ShellScripts {
+    location: Option<PathBuf>,
+}
+
+fn toolbox_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("JetBrains/Toolbox"))
+}
+
+/// Where Toolbox writes its launcher scripts, honoring a custom location from `.settings.json`
+pub fn scripts_dir() -> Option<PathBuf> {
+    Some(scripts_dir_in(&toolbox_dir()?))
+}
+
+fn scripts_dir_in(toolbox: &Path) -> PathBuf {
+    let custom = fs::read_to_string(toolbox.join(".settings.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<Settings>(&s).ok())
+        .and_then(|s| s.shell_scripts)
+        .and_then(|s| s.location);
+    custom.unwrap_or_else(|| toolbox.join("scripts"))
+}
+
+/// Every tool listed in Toolbox's `state.json` that is still on disk. Read once and shared by
+/// all JetBrains families.
+pub fn installed_tools() -> &'static [ToolboxTool] {
+    static TOOLS: OnceLock<Vec<ToolboxTool>> = OnceLock::new();
+    TOOLS.get_or_init(|| {
+        toolbox_dir()
+            .map(|d| read_installed_tools(&d))
+            .unwrap_or_default()
+    })
+}
+
+fn read_installed_tools(toolbox: &Path) -> Vec<ToolboxTool> {
+    let Some(state) = fs::read_to_string(toolbox.join("state.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<State>(&s).ok())
+    else {
+        return Vec::new();
+    };
+
+    let scripts = read_scripts(&scripts_dir_in(toolbox));
+
+    state
+        .tools
+        .into_iter()
+        .filter(|tool| tool.install_location.is_dir())
+        .map(|tool| {
+            let location = tool.install_location.to_string_lossy().into_owned();
+            let script = scripts
+                .iter()
+                .find(|(_, content)| content.contains(&location))
+                .map(|(path, _)| path.clone());
+            let eap = [&tool.display_version, &tool.channel_id, &tool.tag]
+                .iter()
+                .any(|s| s.to_ascii_lowercase().contains("eap"));
+
+            ToolboxTool {
+                install_location: tool.install_location,
+                launch_command: tool.launch_command,
+                script,
+                eap,
+            }
+        })
+        .collect()
+}
+
+/// Toolbox's generated scripts embed the path of the IDE they launch, which is how we tell
+/// which script belongs to which tool
+fn read_scripts(dir: &Path) -> Vec<(PathBuf, String)> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            Some((path, content))
+        })
+        .collect()
+}
+
+/// Candidate install dirs under `apps/`, in both the flat `apps/<tool>` layout and the channel
+/// layout `apps/<tool>/ch-<n>/<build>`, for when `state.json` can't be read
+pub fn app_install_dirs() -> Vec<PathBuf> {
+    toolbox_dir()
+        .map(|d| app_install_dirs_in(&d))
+        .unwrap_or_default()
+}
+
+fn app_install_dirs_in(toolbox: &Path) -> Vec<PathBuf> {
+    let tools = subdirs(&toolbox.join("apps"));
+
+    let channel_builds = tools
+        .iter()
+        .flat_map(|tool| subdirs(tool))
+        .filter(|channel| {
+            channel
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with("ch-"))
+        })
+        .flat_map(|channel| subdirs(&channel))
+        .filter(|build| {
+            build
+                .file_name()
+                .is_some_and(|n| !n.to_string_lossy().ends_with(".plugins"))
+        });
+
+    channel_builds.chain(tools.iter().cloned()).collect()
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tools_with_their_scripts() {
+        let toolbox = tempfile::tempdir().unwrap();
+        let toolbox = toolbox.path();
+        let pycharm = toolbox.join("apps/pycharm-professional");
+        let idea_eap = toolbox.join("apps/intellij-idea-ultimate-eap");
+        fs::create_dir_all(&pycharm).unwrap();
+        fs::create_dir_all(&idea_eap).unwrap();
+        fs::write(
+            toolbox.join("state.json"),
+            serde_json::json!({
+                "tools": [
+                    {"installLocation": pycharm, "launchCommand": "bin/pycharm.sh", "displayVersion": "2025.2"},
+                    {"installLocation": idea_eap, "channelId": "IDEA-U-EAP"},
+                    {"installLocation": toolbox.join("apps/removed")}
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let custom_scripts = toolbox.join("custom-scripts");
+        fs::create_dir(&custom_scripts).unwrap();
+        fs::write(
+            toolbox.join(".settings.json"),
+            serde_json::json!({"shell_scripts": {"location": custom_scripts}}).to_string(),
+        )
+        .unwrap();
+        fs::write(
+            custom_scripts.join("pycharm"),
+            format!(
+                "#!/bin/sh\nexec \"{}/bin/pycharm.sh\" \"$@\"\n",
+                pycharm.display()
+            ),
+        )
+        .unwrap();
+
+        let tools = read_installed_tools(toolbox);
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].install_location, pycharm);
+        assert_eq!(tools[0].launch_command.as_deref(), Some("bin/pycharm.sh"));
+        assert_eq!(tools[0].script, Some(custom_scripts.join("pycharm")));
+        assert!(!tools[0].eap);
+        assert_eq!(tools[1].script, None);
+        assert!(tools[1].eap);
+    }
+
+    #[test]
+    fn no_tools_without_state() {
+        let toolbox = tempfile::tempdir().unwrap();
+        assert!(read_installed_tools(toolbox.path()).is_empty());
+        assert_eq!(
+            scripts_dir_in(toolbox.path()),
+            toolbox.path().join("scripts")
+        );
+    }
+
+    #[test]
+    fn finds_flat_and_channel_install_dirs() {
+        let toolbox = tempfile::tempdir().unwrap();
+        let apps = toolbox.path().join("apps");
+        for dir in [
+            "PyCharm-P/ch-0/252.23892.515",
+            "PyCharm-P/ch-0/252.23892.515.plugins",
+            "pycharm-professional/bin",
+        ] {
+            fs::create_dir_all(apps.join(dir)).unwrap();
+        }
+
+        let mut dirs = app_install_dirs_in(toolbox.path());
+        dirs.sort();
+        assert_eq!(
+            dirs,
+            [
+                apps.join("PyCharm-P"),
+                apps.join("PyCharm-P/ch-0/252.23892.515"),
+                apps.join("pycharm-professional"),
+            ]
+        );
+    }
+}
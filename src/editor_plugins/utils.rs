@@ -1,5 +1,18 @@
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use color_eyre::{Result, eyre::eyre};
+use jsonc_parser::{
+    ParseOptions,
+    cst::{CstObject, CstRootNode},
+};
+
+use crate::journal;
+
+const BLOCK_START: &str = "# >>> hackatime >>>";
+const BLOCK_END: &str = "# <<< hackatime <<<";
+
 pub fn is_process_running(process_name: &str) -> bool {
     #[cfg(target_os = "windows")]
     {
@@ -21,3 +34,326 @@ pub fn is_process_running(process_name: &str) -> bool {
         false
     }
 }
+
+/// Where downloads are cached between runs
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|c| c.join("hackatime-setup"))
+}
+
+/// Returns `content` with any hackatime marked block removed, and whether one was found.
+fn strip_marked_block(content: &str) -> (String, bool) {
+    let mut out = String::with_capacity(content.len());
+    let mut inside = false;
+    let mut found = false;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == BLOCK_START {
+            inside = true;
+            found = true;
+        } else if trimmed == BLOCK_END && inside {
+            inside = false;
+        } else if !inside {
+            out.push_str(line);
+        }
+    }
+
+    (out, found)
+}
+
+/// Writes `body` between hackatime markers at the end of `path`, replacing any previous block.
+pub fn upsert_marked_block(path: &Path, body: &str) -> Result<()> {
+    // Write through symlinks (e.g. dotfile managers) instead of replacing them
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let existing = if path.exists() {
+        fs::read_to_string(path).map_err(|e| eyre!("Failed to read {}: {}", path.display(), e))?
+    } else {
+        String::new()
+    };
+
+    let (mut content, _) = strip_marked_block(&existing);
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(BLOCK_START);
+    content.push('\n');
+    content.push_str(body.trim_end());
+    content.push('\n');
+    content.push_str(BLOCK_END);
+    content.push('\n');
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    journal::record_write(path)?;
+    fs::write(path, content).map_err(|e| eyre!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Removes the hackatime marked block from `path`. Returns whether anything was removed.
+pub fn remove_marked_block(path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    let existing =
+        fs::read_to_string(path).map_err(|e| eyre!("Failed to read {}: {}", path.display(), e))?;
+    let (content, found) = strip_marked_block(&existing);
+    if found {
+        fs::write(path, content).map_err(|e| eyre!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(found)
+}
+
+/// Spelled out rather than relying on the crate defaults: editors like Zed and VS Code accept
+/// all of these in their settings files, so we must too
+fn lenient_parse_options() -> ParseOptions {
+    ParseOptions {
+        allow_comments: true,
+        allow_loose_object_property_names: true,
+        allow_trailing_commas: true,
+        allow_missing_commas: true,
+        allow_single_quoted_strings: true,
+        allow_hexadecimal_numbers: true,
+        allow_unary_plus_numbers: true,
+    }
+}
+
+const BOM: &str = "\u{feff}";
+
+/// Applies `edit` to the root object of a JSON-with-comments settings file, keeping comments and
+/// formatting intact. The file is only rewritten if the edit changed something, and then only
+/// after the result re-parses. The previous version is kept next to it as `<name>.bak`.
+pub fn edit_json_settings(path: &Path, edit: impl FnOnce(&CstObject) -> Result<()>) -> Result<()> {
+    // Write through symlinks (e.g. dotfile managers) instead of replacing them
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let original = if path.exists() {
+        fs::read_to_string(path).map_err(|e| eyre!("Failed to read {}: {}", path.display(), e))?
+    } else {
+        String::new()
+    };
+    let bom = if original.starts_with(BOM) { BOM } else { "" };
+    let content = match original[bom.len()..].trim() {
+        "" => "{}",
+        _ => &original[bom.len()..],
+    };
+
+    let root = CstRootNode::parse(content, &lenient_parse_options())
+        .map_err(|e| eyre!("Invalid {}: {}", path.display(), e))?;
+
+    let root_obj = root
+        .object_value_or_create()
+        .ok_or_else(|| eyre!("{} root must be an object", path.display()))?;
+
+    edit(&root_obj)?;
+
+    let updated = format!("{bom}{root}");
+    if updated == original {
+        return Ok(());
+    }
+
+    CstRootNode::parse(&updated[bom.len()..], &lenient_parse_options())
+        .ok()
+        .filter(|reparsed| reparsed.object_value().is_some())
+        .ok_or_else(|| {
+            eyre!(
+                "Editing {} produced invalid JSON, leaving it untouched",
+                path.display()
+            )
+        })?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    journal::record_write(path)?;
+    if !original.trim().is_empty() {
+        let backup = sibling(path, "bak");
+        journal::record_write(&backup)?;
+        fs::write(&backup, &original)
+            .map_err(|e| eyre!("Failed to back up {}: {}", path.display(), e))?;
+    }
+    atomic_write(path, &updated)
+}
+
+/// `<file name>.<suffix>` next to `path`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Writes to a temp file next to `path` and renames it over, so readers (and crashes) never see
+/// a half-written file. The file keeps its permissions.
+fn atomic_write(path: &Path, content: &str) -> Result<()> {
+    let tmp_path = sibling(path, "hackatime.tmp");
+    fs::write(&tmp_path, content)
+        .and_then(|_| match fs::metadata(path) {
+            Ok(metadata) => fs::set_permissions(&tmp_path, metadata.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            eyre!("Failed to write {}: {}", path.display(), e)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor_plugins::{Zed, ZedSettings};
+
+    fn testdata(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/editor_plugins/testdata/settings")
+            .join(name)
+    }
+
+    /// Zed setup without extra settings, whose edit the golden files hold
+    fn zed() -> Zed {
+        Zed::channels(&ZedSettings::default()).remove(0)
+    }
+
+    /// Runs Zed setup on a copy of `<name>.json` and compares it to `<name>.expected.json`
+    fn assert_golden(name: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::copy(testdata(&format!("{name}.json")), &path).unwrap();
+
+        zed().add_extension_to_settings(&path).unwrap();
+
+        let expected = fs::read_to_string(testdata(&format!("{name}.expected.json"))).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+    }
+
+    #[test]
+    fn keeps_comments() {
+        assert_golden("comments");
+    }
+
+    #[test]
+    fn accepts_trailing_commas() {
+        assert_golden("trailing_commas");
+    }
+
+    #[test]
+    fn fills_empty_file() {
+        assert_golden("empty");
+    }
+
+    #[test]
+    fn overrides_disabled_wakatime() {
+        assert_golden("wakatime_false");
+    }
+
+    #[test]
+    fn keeps_bom() {
+        assert_golden("bom");
+    }
+
+    #[test]
+    fn creates_missing_file_without_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zed/settings.json");
+
+        zed().add_extension_to_settings(&path).unwrap();
+
+        let expected = fs::read_to_string(testdata("empty.expected.json")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+        assert!(!sibling(&path, "bak").exists());
+    }
+
+    #[test]
+    fn backs_up_previous_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::copy(testdata("comments.json"), &path).unwrap();
+
+        zed().add_extension_to_settings(&path).unwrap();
+
+        let original = fs::read_to_string(testdata("comments.json")).unwrap();
+        assert_eq!(fs::read_to_string(sibling(&path, "bak")).unwrap(), original);
+    }
+
+    #[test]
+    fn leaves_unchanged_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::copy(testdata("comments.expected.json"), &path).unwrap();
+
+        zed().add_extension_to_settings(&path).unwrap();
+
+        assert!(!sibling(&path, "bak").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("dotfiles-settings.json");
+        let link = dir.path().join("settings.json");
+        fs::copy(testdata("comments.json"), &target).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        zed().add_extension_to_settings(&link).unwrap();
+
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        let expected = fs::read_to_string(testdata("comments.expected.json")).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::copy(testdata("comments.json"), &path).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        zed().add_extension_to_settings(&path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn upserts_marked_block_through_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("dotfiles-bashrc");
+        let link = dir.path().join(".bashrc");
+        fs::write(&target, "alias ll='ls -l'\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        upsert_marked_block(&link, "echo hi").unwrap();
+
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            format!("alias ll='ls -l'\n{BLOCK_START}\necho hi\n{BLOCK_END}\n")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_json_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::write(&path, "{ \"theme\": ").unwrap();
+
+        assert!(zed().add_extension_to_settings(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ \"theme\": ");
+    }
+
+    #[test]
+    fn rejects_non_object_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::write(&path, "[]").unwrap();
+
+        assert!(zed().add_extension_to_settings(&path).is_err());
+    }
+}
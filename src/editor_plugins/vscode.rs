@@ -1,125 +1,504 @@
+use std::ffi::OsStr;
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
 
 use color_eyre::{Result, eyre::eyre};
+use jsonc_parser::json;
+use serde::Deserialize;
 use which::which;
 
-use super::EditorPlugin;
+use super::sandbox::Sandbox;
+use super::utils::{cache_dir, edit_json_settings};
+use super::{EditorPlugin, SetupOptions, nix};
+use crate::{api, journal};
 
+pub const EXTENSION_PUBLISHER: &str = "WakaTime";
+pub const EXTENSION_NAME: &str = "vscode-wakatime";
+/// Time allowed for fetching the `.vsix`, which can take a while on slow connections
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The newest version of the extension in `extensions_dir`, going by its
+/// `wakatime.vscode-wakatime-<version>` folders
+pub fn extension_version(extensions_dir: &Path) -> Option<String> {
+    let prefix = format!("{EXTENSION_PUBLISHER}.{EXTENSION_NAME}-").to_ascii_lowercase();
+    fs::read_dir(extensions_dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+            name.strip_prefix(&prefix).map(str::to_string)
+        })
+        .max_by_key(|version| version_key(version))
+}
+
+/// The numeric components of a version like `25.3.0` or `1.2.3-linux-x64`, so versions
+/// compare by number rather than as strings
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// An entry of a profile's `extensions.json`
+#[derive(Deserialize)]
+struct ProfileExtension {
+    identifier: ExtensionIdentifier,
+    #[serde(default)]
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct ExtensionIdentifier {
+    id: String,
+}
+
+/// The extension's version in a non-default profile. Profiles share the extensions dir, so
+/// only the profile's own `extensions.json` says whether it has the extension.
+fn profile_extension_version(profile_dir: &Path) -> Option<String> {
+    let json = fs::read_to_string(profile_dir.join("extensions.json")).ok()?;
+    let extensions: Vec<ProfileExtension> = serde_json::from_str(&json).ok()?;
+    let id = format!("{EXTENSION_PUBLISHER}.{EXTENSION_NAME}");
+    extensions
+        .into_iter()
+        .find(|e| e.identifier.id.eq_ignore_ascii_case(&id))
+        .map(|e| e.version)
+}
+
+#[derive(Clone, Deserialize)]
 pub struct VsCodeFamily {
-    pub name: &'static str,
-    pub config_subdir: &'static str,
-    pub cli_command: &'static str,
-    #[allow(dead_code)]
-    pub macos_app_name: &'static str,
-    #[allow(dead_code)]
-    pub windows_app_folder: &'static str,
+    pub name: String,
+    /// Dirs under the home directory holding `extensions`, e.g. ".vscode"
+    pub config_subdirs: Vec<String>,
+    /// CLI names to look for, in order of preference
+    pub cli_commands: Vec<String>,
+    /// Folder name of the user data dir inside the OS config dir, e.g. "Code".
+    /// `None` for servers that keep their data elsewhere.
+    #[serde(default)]
+    pub user_data_folder: Option<String>,
+    #[serde(default)]
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub macos_app_names: Vec<String>,
+    #[serde(default)]
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub windows_app_folders: Vec<String>,
+    /// Flatpak app ids this editor is published under, e.g. "com.visualstudio.code"
+    #[serde(default)]
+    pub flatpak_ids: Vec<String>,
+    /// Snap names this editor is published under
+    #[serde(default)]
+    pub snap_names: Vec<String>,
+    /// nixpkgs attribute for home-manager's `programs.vscode.package`, e.g. "vscodium".
+    /// Defaults to the name of the store package the CLI comes from.
+    #[serde(default)]
+    pub nix_package: Option<String>,
+}
+
+/// WakaTime keys that advanced setup writes into the editor's own `settings.json`
+#[derive(Clone, Default)]
+pub struct VsCodeSettings {
+    pub status_bar_enabled: Option<bool>,
+    pub debug: Option<bool>,
+}
+
+impl VsCodeSettings {
+    const STATUS_BAR_ENABLED: &str = "wakatime.status_bar_enabled";
+    const DEBUG: &str = "wakatime.debug";
+    const KEYS: [&str; 2] = [Self::STATUS_BAR_ENABLED, Self::DEBUG];
+
+    fn entries(&self) -> Vec<(&'static str, bool)> {
+        [
+            (Self::STATUS_BAR_ENABLED, self.status_bar_enabled),
+            (Self::DEBUG, self.debug),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct Storage {
+    #[serde(rename = "userDataProfiles", default)]
+    user_data_profiles: Vec<StoredProfile>,
+}
+
+#[derive(Clone, Deserialize)]
+struct StoredProfile {
+    location: String,
+    name: String,
 }
 
 impl VsCodeFamily {
-    fn extensions_dir(&self) -> Option<PathBuf> {
-        let home = dirs::home_dir()?;
-        Some(home.join(self.config_subdir).join("extensions"))
+    /// One target for the default profile plus one per additional profile found on disk, for
+    /// the host install and each Flatpak or Snap one
+    pub fn targets(self, options: &SetupOptions) -> Vec<Box<dyn EditorPlugin>> {
+        // Custom dirs only make sense for the one editor they were given for
+        let custom = self.name.eq_ignore_ascii_case(&options.vscode_family);
+        let custom_user_data_dir = options.vscode_user_data_dir.clone().filter(|_| custom);
+        let custom_extensions_dir = options.vscode_extensions_dir.clone().filter(|_| custom);
+
+        let (user_data_dir, extensions_dir) = match custom_user_data_dir {
+            Some(dir) => (Some(dir), custom_extensions_dir),
+            None => match self.portable_data_dir() {
+                Some(data) => (Some(data.join("user-data")), Some(data.join("extensions"))),
+                None => (None, custom_extensions_dir),
+            },
+        };
+
+        let mut targets = self.profile_targets(options, None, user_data_dir, extensions_dir);
+        for sandbox in Sandbox::installed(&self.flatpak_ids, &self.snap_names) {
+            targets.extend(self.profile_targets(options, Some(sandbox), None, None));
+        }
+        targets
+    }
+
+    fn profile_targets(
+        &self,
+        options: &SetupOptions,
+        sandbox: Option<Sandbox>,
+        user_data_dir: Option<PathBuf>,
+        extensions_dir: Option<PathBuf>,
+    ) -> Vec<Box<dyn EditorPlugin>> {
+        let profiles = user_data_dir
+            .clone()
+            .or_else(|| self.default_user_data_dir(sandbox.as_ref()))
+            .map(|dir| Self::profiles(&dir))
+            .unwrap_or_default();
+
+        std::iter::once(None)
+            .chain(profiles.into_iter().map(Some))
+            .map(|profile| -> Box<dyn EditorPlugin> {
+                Box::new(VsCodeTarget {
+                    family: self.clone(),
+                    sandbox: sandbox.clone(),
+                    user_data_dir: user_data_dir.clone(),
+                    extensions_dir: extensions_dir.clone(),
+                    profile,
+                    gallery_url: options.vsix_gallery_url.clone(),
+                    vsix_path: options.vsix_path.clone(),
+                    settings: options.vscode_settings.clone(),
+                    nix_snippet_dir: options.nix_snippet_dir.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// `<config dir>/<user_data_folder>`, using the sandbox's config dir if there is one
+    fn default_user_data_dir(&self, sandbox: Option<&Sandbox>) -> Option<PathBuf> {
+        let config_dir = match sandbox {
+            Some(sandbox) => sandbox.config_dir(),
+            None => dirs::config_dir(),
+        };
+        Some(config_dir?.join(self.user_data_folder.as_ref()?))
+    }
+
+    /// The `data` folder of a portable install, which replaces the usual user data and
+    /// extensions dirs
+    fn portable_data_dir(&self) -> Option<PathBuf> {
+        #[cfg(target_os = "macos")]
+        {
+            let (command, cli) = self.find_cli_with_name()?;
+            let cli = fs::canonicalize(cli).ok()?;
+            // <parent>/<App>.app/Contents/Resources/app/bin/<cli>
+            let app_bundle = cli.ancestors().nth(5)?;
+            let data = app_bundle
+                .parent()?
+                .join(format!("{command}-portable-data"));
+            data.is_dir().then_some(data)
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        {
+            let cli = fs::canonicalize(self.find_cli()?).ok()?;
+            // <root>/bin/<cli>. AppImages and other launchers don't sit in a `bin` of their own.
+            let bin = cli.parent().filter(|dir| dir.ends_with("bin"))?;
+            let data = bin.parent()?.join("data");
+            data.is_dir().then_some(data)
+        }
+    }
+
+    /// Named profiles from `User/globalStorage/storage.json` that still have a profile dir
+    fn profiles(user_data_dir: &Path) -> Vec<StoredProfile> {
+        let user_dir = user_data_dir.join("User");
+        let storage = fs::read_to_string(user_dir.join("globalStorage/storage.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<Storage>(&s).ok());
+
+        storage
+            .map(|s| s.user_data_profiles)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| user_dir.join("profiles").join(&p.location).is_dir())
+            .collect()
+    }
+
+    /// The first config dir that exists, or the preferred one if none do, under the sandbox's
+    /// home if there is one
+    fn config_dir(&self, sandbox: Option<&Sandbox>) -> Option<PathBuf> {
+        let home = match sandbox {
+            Some(sandbox) => sandbox.home(),
+            None => dirs::home_dir(),
+        }?;
+        let dirs: Vec<PathBuf> = self.config_subdirs.iter().map(|d| home.join(d)).collect();
+        dirs.iter().find(|d| d.exists()).or(dirs.first()).cloned()
+    }
+
+    fn extensions_dir(&self, sandbox: Option<&Sandbox>) -> Option<PathBuf> {
+        Some(self.config_dir(sandbox)?.join("extensions"))
     }
 
-    fn get_fallback_paths(&self) -> Vec<PathBuf> {
+    fn get_fallback_paths(&self, cli_command: &str) -> Vec<PathBuf> {
         let mut paths = Vec::new();
 
+        #[cfg(not(target_os = "windows"))]
+        {
+            // `nix profile`, home-manager and NixOS installs
+            for dir in nix::profile_bin_dirs() {
+                paths.push(dir.join(cli_command));
+            }
+        }
+
         #[cfg(target_os = "macos")]
         {
-            let app_path = format!(
-                "Applications/{}.app/Contents/Resources/app/bin/{}",
-                self.macos_app_name, self.cli_command
-            );
+            for app_name in &self.macos_app_names {
+                let app_path = format!(
+                    "Applications/{}.app/Contents/Resources/app/bin/{}",
+                    app_name, cli_command
+                );
 
-            paths.push(PathBuf::from(format!("/{app_path}")));
-            if let Some(home) = dirs::home_dir() {
-                paths.push(home.join(app_path));
+                paths.push(PathBuf::from(format!("/{app_path}")));
+                if let Some(home) = dirs::home_dir() {
+                    paths.push(home.join(app_path));
+                }
             }
         }
 
         #[cfg(target_os = "linux")]
         {
             // Common Linux locations
-            paths.push(PathBuf::from(format!("/usr/bin/{}", self.cli_command)));
-            paths.push(PathBuf::from(format!(
-                "/usr/local/bin/{}",
-                self.cli_command
-            )));
-            paths.push(PathBuf::from(format!("/snap/bin/{}", self.cli_command)));
+            paths.push(PathBuf::from(format!("/usr/bin/{}", cli_command)));
+            paths.push(PathBuf::from(format!("/usr/local/bin/{}", cli_command)));
+            paths.push(PathBuf::from(format!("/snap/bin/{}", cli_command)));
             if let Some(home) = dirs::home_dir() {
-                paths.push(home.join(format!(".local/bin/{}", self.cli_command)));
+                paths.push(home.join(format!(".local/bin/{}", cli_command)));
             }
+            // AppImages, unpacked tarballs and `.desktop` entries
+            paths.extend(super::launchers::launchers_named(cli_command));
         }
 
         #[cfg(target_os = "windows")]
         {
             // Windows users might install to LocalAppData or Program Files
-            let binary = format!("{}.cmd", self.cli_command); // Explicitly look for .cmd
+            let binary = format!("{}.cmd", cli_command); // Explicitly look for .cmd
 
-            if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-                paths.push(PathBuf::from(format!(
-                    "{}\\Programs\\{}\\bin\\{}",
-                    localappdata, self.windows_app_folder, binary
-                )));
-            }
+            for app_folder in &self.windows_app_folders {
+                if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
+                    paths.push(PathBuf::from(format!(
+                        "{}\\Programs\\{}\\bin\\{}",
+                        localappdata, app_folder, binary
+                    )));
+                }
 
-            if let Ok(program_files) = std::env::var("ProgramFiles") {
-                paths.push(PathBuf::from(format!(
-                    "{}\\{}\\bin\\{}",
-                    program_files, self.windows_app_folder, binary
-                )));
-            }
+                if let Ok(program_files) = std::env::var("ProgramFiles") {
+                    paths.push(PathBuf::from(format!(
+                        "{}\\{}\\bin\\{}",
+                        program_files, app_folder, binary
+                    )));
+                }
 
-            if let Ok(program_files_x86) = std::env::var("ProgramFiles(x86)") {
-                paths.push(PathBuf::from(format!(
-                    "{}\\{}\\bin\\{}",
-                    program_files_x86, self.windows_app_folder, binary
-                )));
+                if let Ok(program_files_x86) = std::env::var("ProgramFiles(x86)") {
+                    paths.push(PathBuf::from(format!(
+                        "{}\\{}\\bin\\{}",
+                        program_files_x86, app_folder, binary
+                    )));
+                }
             }
         }
 
         paths
     }
 
-    fn find_cli(&self) -> Option<PathBuf> {
+    /// The CLI path along with which of `cli_commands` it matched
+    fn find_cli_with_name(&self) -> Option<(&str, PathBuf)> {
         // 1. Try to find it in the System PATH using the 'which' crate.
         // This handles .cmd, .exe, and .bat automatically on Windows.
-        if let Ok(path) = which(self.cli_command) {
-            return Some(path);
+        for command in &self.cli_commands {
+            if let Ok(path) = which(command) {
+                return Some((command.as_str(), path));
+            }
         }
 
         // 2. Fallback to hardcoded paths if not in PATH
-        self.get_fallback_paths()
-            .into_iter()
-            .find(|path| path.exists())
+        self.cli_commands.iter().find_map(|command| {
+            self.get_fallback_paths(command)
+                .into_iter()
+                .find(|path| path.exists())
+                .map(|path| (command.as_str(), path))
+        })
+    }
+
+    fn find_cli(&self) -> Option<PathBuf> {
+        self.find_cli_with_name().map(|(_, path)| path)
     }
 }
 
-impl EditorPlugin for VsCodeFamily {
-    fn name(&self) -> String {
-        self.name.to_string()
+/// A single VS Code-family install location and profile to put the extension into
+pub struct VsCodeTarget {
+    family: VsCodeFamily,
+    /// Flatpak or Snap the editor runs in, `None` for a host install
+    sandbox: Option<Sandbox>,
+    user_data_dir: Option<PathBuf>,
+    extensions_dir: Option<PathBuf>,
+    profile: Option<StoredProfile>,
+    gallery_url: String,
+    vsix_path: Option<PathBuf>,
+    settings: VsCodeSettings,
+    nix_snippet_dir: Option<PathBuf>,
+}
+
+impl VsCodeTarget {
+    /// The nixpkgs attribute of the editor if its CLI lives in the Nix store, where the
+    /// extension has to be added declaratively
+    fn nix_package(&self) -> Option<String> {
+        if self.sandbox.is_some() {
+            return None;
+        }
+        let store_package = nix::store_package(&self.family.find_cli()?)?;
+        Some(
+            self.family
+                .nix_package
+                .clone()
+                .unwrap_or_else(|| nix::pname(&store_package).to_string()),
+        )
     }
 
-    fn is_installed(&self) -> bool {
-        // It's installed if we can find the CLI OR the extension folder exists
-        self.find_cli().is_some()
-            || self
-                .extensions_dir()
-                .and_then(|d| d.parent().map(Path::exists))
-                .unwrap_or(false)
+    /// home-manager options that add the extension (and settings) to this target's profile
+    fn nix_module(&self, package: &str) -> String {
+        let profile = self.profile.as_ref().map_or("default", |p| p.name.as_str());
+        let mut lines = vec![
+            "  programs.vscode = {".to_string(),
+            "    enable = true;".to_string(),
+            format!("    package = pkgs.{package};"),
+            format!(
+                "    profiles.\"{profile}\".extensions = [ pkgs.vscode-extensions.{EXTENSION_PUBLISHER}.{EXTENSION_NAME} ];"
+            ),
+        ];
+        for (key, value) in self.settings.entries() {
+            lines.push(format!(
+                "    profiles.\"{profile}\".userSettings.\"{key}\" = {value};"
+            ));
+        }
+        lines.push("  };".to_string());
+        lines.join("\n")
     }
 
-    fn install(&self) -> Result<()> {
-        let cli_path = self.find_cli().ok_or_else(|| {
-            eyre!(
-                "{} CLI not found. Is it installed and in your PATH?",
-                self.name
-            )
-        })?;
+    fn user_data_dir(&self) -> Option<PathBuf> {
+        self.user_data_dir
+            .clone()
+            .or_else(|| self.family.default_user_data_dir(self.sandbox.as_ref()))
+    }
+
+    /// `<user data dir>/User`, if the user data dir is known
+    fn user_dir(&self) -> Option<PathBuf> {
+        Some(self.user_data_dir()?.join("User"))
+    }
+
+    /// `User/profiles/<location>` for targets with a non-default profile
+    fn profile_dir(&self) -> Option<PathBuf> {
+        let profile = self.profile.as_ref()?;
+        Some(self.user_dir()?.join("profiles").join(&profile.location))
+    }
 
-        // Prepare the command
-        let mut cmd;
+    /// The `settings.json` of this target's profile, if the user data dir is known
+    fn settings_path(&self) -> Option<PathBuf> {
+        match &self.profile {
+            Some(_) => Some(self.profile_dir()?.join("settings.json")),
+            None => Some(self.user_dir()?.join("settings.json")),
+        }
+    }
+
+    fn apply_settings(&self) -> Result<()> {
+        let entries = self.settings.entries();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let Some(settings_path) = self.settings_path() else {
+            return Ok(());
+        };
+
+        edit_json_settings(&settings_path, |root_obj| {
+            for (key, value) in entries {
+                match root_obj.get(key) {
+                    None => {
+                        root_obj.append(key, json!(value));
+                    }
+                    Some(prop) => prop.set_value(json!(value)),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_settings(&self) -> Result<()> {
+        let Some(settings_path) = self.settings_path().filter(|p| p.exists()) else {
+            return Ok(());
+        };
+
+        edit_json_settings(&settings_path, |root_obj| {
+            for key in VsCodeSettings::KEYS {
+                if let Some(prop) = root_obj.get(key) {
+                    prop.remove();
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// The editor CLI invocation for `action` (e.g. `--install-extension`) with this target's
+    /// layout and profile
+    fn cli_action(&self, action: &str, extension: &OsStr) -> Result<Command> {
+        let mut cmd = self.cli_command()?;
+
+        if let Some(dir) = &self.user_data_dir {
+            cmd.arg("--user-data-dir").arg(dir);
+        }
+        if let Some(dir) = &self.extensions_dir {
+            cmd.arg("--extensions-dir").arg(dir);
+        }
+        if let Some(profile) = &self.profile {
+            cmd.args(["--profile", &profile.name]);
+        }
+
+        cmd.arg(action).arg(extension);
+        Ok(cmd)
+    }
+
+    fn run_cli(&self, action: &str, extension: &OsStr) -> Result<ExitStatus> {
+        let mut cmd = self.cli_action(action, extension)?;
+        cmd.stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_err(|e| eyre!("Failed to execute {:?}: {}", cmd.get_program(), e))
+    }
+
+    /// The editor CLI, run inside the sandbox for Flatpak and Snap installs
+    fn cli_command(&self) -> Result<Command> {
+        if let Some(sandbox) = &self.sandbox {
+            let cli = self.family.cli_commands.first().map(String::as_str);
+            return Ok(sandbox.command(cli));
+        }
+
+        let name = &self.family.name;
+        let cli_path = self
+            .family
+            .find_cli()
+            .ok_or_else(|| eyre!("{} CLI not found. Is it installed and in your PATH?", name))?;
 
         #[cfg(target_os = "windows")]
         {
@@ -128,31 +507,461 @@ impl EditorPlugin for VsCodeFamily {
             // Executing batch files directly via Command::new sometimes fails
             // with error 193 if the OS environment isn't perfect.
             // We wrap it in `cmd /C` to guarantee execution.
-            cmd = Command::new("cmd");
+            let mut cmd = Command::new("cmd");
             cmd.arg("/C");
-            cmd.arg(&cli_path);
+            cmd.arg(cli_path);
+            Ok(cmd)
         }
 
         #[cfg(not(target_os = "windows"))]
         {
-            cmd = Command::new(&cli_path);
+            Ok(Command::new(cli_path))
         }
+    }
 
-        let status = cmd
-            .args(["--install-extension", "WakaTime.vscode-wakatime"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .map_err(|e| eyre!("Failed to execute {:?}: {}", cli_path, e))?;
+    /// The `.vsix` to fall back to: the one given on the command line, or a cached download
+    /// from the gallery
+    fn fallback_vsix(&self) -> Result<PathBuf> {
+        if let Some(path) = &self.vsix_path {
+            return Ok(path.clone());
+        }
 
+        let cache_dir = cache_dir()
+            .ok_or_else(|| eyre!("Could not determine cache directory"))?
+            .join("vsix");
+
+        match download_vsix(&self.gallery_url, &cache_dir) {
+            Ok(path) => Ok(path),
+            // Offline: any earlier download is better than nothing
+            Err(e) => newest_cached_vsix(&cache_dir).ok_or(e),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GalleryExtension {
+    version: String,
+    files: GalleryFiles,
+}
+
+#[derive(Deserialize)]
+struct GalleryFiles {
+    download: String,
+}
+
+/// Downloads the latest extension from an Open VSX-compatible gallery into `cache_dir`,
+/// reusing an earlier download of the same version
+fn download_vsix(gallery_url: &str, cache_dir: &Path) -> Result<PathBuf> {
+    let client = api::client_builder().timeout(DOWNLOAD_TIMEOUT).build()?;
+    let metadata_url = format!(
+        "{}/api/{}/{}/latest",
+        gallery_url.trim_end_matches('/'),
+        EXTENSION_PUBLISHER,
+        EXTENSION_NAME
+    );
+
+    let extension: GalleryExtension = client
+        .get(&metadata_url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.json())
+        .map_err(|e| {
+            eyre!(
+                "Failed to look up WakaTime extension on {}: {}",
+                gallery_url,
+                e
+            )
+        })?;
+
+    let vsix_path = cache_dir.join(format!(
+        "{}.{}-{}.vsix",
+        EXTENSION_PUBLISHER, EXTENSION_NAME, extension.version
+    ));
+    if vsix_path.exists() {
+        return Ok(vsix_path);
+    }
+
+    let bytes = client
+        .get(&extension.files.download)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.bytes())
+        .map_err(|e| eyre!("Failed to download WakaTime extension: {}", e))?;
+
+    fs::create_dir_all(cache_dir)?;
+    let tmp_path = vsix_path.with_extension("vsix.part");
+    fs::write(&tmp_path, &bytes)
+        .map_err(|e| eyre!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, &vsix_path)?;
+
+    Ok(vsix_path)
+}
+
+fn newest_cached_vsix(cache_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(cache_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "vsix"))
+        .max_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+}
+
+impl EditorPlugin for VsCodeTarget {
+    fn name(&self) -> String {
+        let mut qualifiers = Vec::new();
+        if let Some(sandbox) = &self.sandbox {
+            qualifiers.push(sandbox.kind().to_string());
+        }
+        if let Some(profile) = &self.profile {
+            qualifiers.push(format!("{} profile", profile.name));
+        }
+
+        if qualifiers.is_empty() {
+            self.family.name.clone()
+        } else {
+            format!("{} ({})", self.family.name, qualifiers.join(", "))
+        }
+    }
+
+    fn is_installed(&self) -> bool {
+        // Only created for sandboxes the package manager reports as installed
+        if self.sandbox.is_some() {
+            return true;
+        }
+
+        // It's installed if we can find the CLI or the editor's own user data dir exists. The
+        // extensions dir under the home only counts for editors without a user data dir, since
+        // others may share it (Code - OSS and VSCodium both use `~/.vscode-oss`).
+        if self.family.find_cli().is_some() || self.user_data_dir().is_some_and(|d| d.exists()) {
+            return true;
+        }
+        (self.family.user_data_folder.is_none() || self.extensions_dir.is_some())
+            && self
+                .install_path()
+                .and_then(|d| d.parent().map(Path::exists))
+                .unwrap_or(false)
+    }
+
+    fn install_path(&self) -> Option<PathBuf> {
+        self.extensions_dir
+            .clone()
+            .or_else(|| self.family.extensions_dir(self.sandbox.as_ref()))
+    }
+
+    fn plugin_version(&self) -> Option<String> {
+        match &self.profile {
+            Some(_) => profile_extension_version(&self.profile_dir()?),
+            None => extension_version(&self.install_path()?),
+        }
+    }
+
+    fn install(&self) -> Result<()> {
+        if let Some(package) = self.nix_package() {
+            return nix::emit_module(
+                &self.name(),
+                &self.nix_module(&package),
+                self.nix_snippet_dir.as_deref(),
+            );
+        }
+
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.share_wakatime_config()?;
+        }
+
+        let extension_id = format!("{EXTENSION_PUBLISHER}.{EXTENSION_NAME}");
+        // Reinstalling an extension that was already there isn't something to undo
+        if self.plugin_version().is_none() {
+            journal::record_command(
+                &self.cli_action("--uninstall-extension", extension_id.as_ref())?,
+            )?;
+        }
+
+        let status = self.run_cli("--install-extension", extension_id.as_ref())?;
+        if status.success() {
+            return self.apply_settings();
+        }
+
+        // Forks pointed at Open VSX or a custom gallery may not resolve the marketplace id
+        let vsix_path = self.fallback_vsix().map_err(|e| {
+            eyre!(
+                "Failed to install WakaTime extension for {} (exit code: {:?}), and the fallback failed: {}",
+                self.name(),
+                status.code(),
+                e
+            )
+        })?;
+
+        let status = self.run_cli("--install-extension", vsix_path.as_os_str())?;
+        if status.success() {
+            self.apply_settings()
+        } else {
+            Err(eyre!(
+                "Failed to install WakaTime extension for {} from {}. Exit code: {:?}",
+                self.name(),
+                vsix_path.display(),
+                status.code()
+            ))
+        }
+    }
+
+    fn uninstall(&self) -> Result<()> {
+        if self.nix_package().is_some() {
+            return nix::remove_module(&self.name(), self.nix_snippet_dir.as_deref());
+        }
+
+        self.remove_settings()?;
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.unshare_wakatime_config()?;
+        }
+
+        let extension_id = format!("{EXTENSION_PUBLISHER}.{EXTENSION_NAME}");
+        let status = self.run_cli("--uninstall-extension", extension_id.as_ref())?;
         if status.success() {
             Ok(())
         } else {
             Err(eyre!(
-                "Failed to install WakaTime extension for {}. Exit code: {:?}",
-                self.name,
+                "Failed to uninstall WakaTime extension for {}. Exit code: {:?}",
+                self.name(),
                 status.code()
             ))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::tests::mock_server;
+
+    fn target(family: &str, user_data_dir: &Path, extensions_dir: Option<&Path>) -> VsCodeTarget {
+        VsCodeTarget {
+            family: serde_json::from_str(family).unwrap(),
+            sandbox: None,
+            user_data_dir: Some(user_data_dir.to_path_buf()),
+            extensions_dir: extensions_dir.map(Path::to_path_buf),
+            profile: None,
+            gallery_url: String::new(),
+            vsix_path: None,
+            settings: VsCodeSettings::default(),
+            nix_snippet_dir: None,
+        }
+    }
+
+    const FAMILY: &str = r#"{
+        "name": "VS Code",
+        "config_subdirs": [".vscode"],
+        "cli_commands": ["hackatime-test-missing-code"],
+        "user_data_folder": "Code"
+    }"#;
+
+    #[test]
+    fn writes_and_removes_settings() {
+        let user_data_dir = tempfile::tempdir().unwrap();
+        let settings_path = user_data_dir.path().join("User/settings.json");
+        fs::create_dir_all(settings_path.parent().unwrap()).unwrap();
+        fs::write(
+            &settings_path,
+            "{\n  // theme\n  \"workbench.colorTheme\": \"Default Dark+\",\n  \"wakatime.debug\": true\n}\n",
+        )
+        .unwrap();
+
+        let mut target = target(FAMILY, user_data_dir.path(), None);
+        target.settings = VsCodeSettings {
+            status_bar_enabled: Some(false),
+            debug: Some(false),
+        };
+        target.apply_settings().unwrap();
+        let settings = fs::read_to_string(&settings_path).unwrap();
+        assert!(settings.contains("// theme"));
+        assert!(settings.contains("\"wakatime.debug\": false"));
+        assert!(settings.contains("\"wakatime.status_bar_enabled\": false"));
+
+        target.remove_settings().unwrap();
+        let settings = fs::read_to_string(&settings_path).unwrap();
+        assert!(settings.contains("\"workbench.colorTheme\": \"Default Dark+\""));
+        assert!(!settings.contains("wakatime"));
+    }
+
+    #[test]
+    fn leaves_settings_alone_without_options() {
+        let user_data_dir = tempfile::tempdir().unwrap();
+        let target = target(FAMILY, user_data_dir.path(), None);
+        target.apply_settings().unwrap();
+        target.remove_settings().unwrap();
+        assert!(!user_data_dir.path().join("User/settings.json").exists());
+    }
+
+    #[test]
+    fn writes_profile_settings_into_profile_dir() {
+        let user_data_dir = tempfile::tempdir().unwrap();
+        let mut target = target(FAMILY, user_data_dir.path(), None);
+        target.profile = Some(StoredProfile {
+            location: "-5a1b2c3d".to_string(),
+            name: "Work".to_string(),
+        });
+        target.settings.debug = Some(true);
+        target.apply_settings().unwrap();
+
+        let settings = fs::read_to_string(
+            user_data_dir
+                .path()
+                .join("User/profiles/-5a1b2c3d/settings.json"),
+        )
+        .unwrap();
+        assert!(settings.contains("\"wakatime.debug\": true"));
+        assert!(!user_data_dir.path().join("User/settings.json").exists());
+    }
+
+    #[test]
+    fn downloads_vsix_from_gallery_once() {
+        let (files_url, files) = mock_server(vec![(200, "application/octet-stream", "vsix")]);
+        let metadata = format!(
+            r#"{{"version": "25.3.0", "files": {{"download": "{files_url}/wakatime.vsix"}}}}"#
+        );
+        let (gallery_url, gallery) = mock_server(vec![
+            (200, "application/json", metadata.clone()),
+            (200, "application/json", metadata),
+        ]);
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        let vsix = download_vsix(&gallery_url, cache_dir.path()).unwrap();
+        assert_eq!(
+            vsix,
+            cache_dir
+                .path()
+                .join("WakaTime.vscode-wakatime-25.3.0.vsix")
+        );
+        assert_eq!(fs::read_to_string(&vsix).unwrap(), "vsix");
+        // The same version again is served from the cache
+        assert_eq!(download_vsix(&gallery_url, cache_dir.path()).unwrap(), vsix);
+
+        let requests = gallery.join().unwrap();
+        assert_eq!(
+            requests[0].line,
+            "GET /api/WakaTime/vscode-wakatime/latest HTTP/1.1"
+        );
+        assert!(requests[0].header("user-agent").is_some());
+        assert_eq!(files.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn shared_extensions_dir_does_not_mean_installed() {
+        let dir = tempfile::tempdir().unwrap();
+        let extensions_dir = dir.path().join(".vscode-oss/extensions");
+        fs::create_dir_all(&extensions_dir).unwrap();
+        let family = r#"{
+            "name": "Code - OSS",
+            "config_subdirs": [".vscode-oss"],
+            "cli_commands": ["hackatime-test-missing-code-oss"],
+            "user_data_folder": "Code - OSS"
+        }"#;
+
+        let user_data_dir = dir.path().join("Code - OSS");
+        let code_oss = target(family, &user_data_dir, None);
+        assert!(!code_oss.is_installed());
+
+        fs::create_dir(&user_data_dir).unwrap();
+        assert!(code_oss.is_installed());
+
+        // A custom extensions dir was given for this editor, so it does count
+        let custom = target(family, &dir.path().join("missing"), Some(&extensions_dir));
+        assert!(custom.is_installed());
+    }
+
+    #[test]
+    fn picks_newest_extension_version_by_number() {
+        let extensions_dir = tempfile::tempdir().unwrap();
+        for version in ["9.0.1", "25.3.0", "25.10.0-linux-x64", "3.0.0"] {
+            fs::create_dir(
+                extensions_dir
+                    .path()
+                    .join(format!("wakatime.vscode-wakatime-{version}")),
+            )
+            .unwrap();
+        }
+        fs::create_dir(extensions_dir.path().join("ms-python.python-2024.8.0")).unwrap();
+
+        assert_eq!(
+            extension_version(extensions_dir.path()),
+            Some("25.10.0-linux-x64".to_string())
+        );
+    }
+
+    #[test]
+    fn no_extension_version_without_extension_folder() {
+        let extensions_dir = tempfile::tempdir().unwrap();
+        assert_eq!(extension_version(extensions_dir.path()), None);
+        assert_eq!(
+            extension_version(&extensions_dir.path().join("missing")),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_profiles_from_storage_json() {
+        let user_data_dir = tempfile::tempdir().unwrap();
+        let user_dir = user_data_dir.path().join("User");
+        fs::create_dir_all(user_dir.join("globalStorage")).unwrap();
+        fs::create_dir_all(user_dir.join("profiles/-5a1b2c3d")).unwrap();
+        fs::write(
+            user_dir.join("globalStorage/storage.json"),
+            r#"{
+                "theme": "vs-dark",
+                "userDataProfiles": [
+                    {"location": "-5a1b2c3d", "name": "Work", "icon": "briefcase"},
+                    {"location": "-deleted", "name": "Gone"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let profiles = VsCodeFamily::profiles(user_data_dir.path());
+        let names: Vec<_> = profiles
+            .iter()
+            .map(|p| (p.name.as_str(), p.location.as_str()))
+            .collect();
+        assert_eq!(names, [("Work", "-5a1b2c3d")]);
+    }
+
+    #[test]
+    fn no_profiles_without_storage_json() {
+        let user_data_dir = tempfile::tempdir().unwrap();
+        assert!(VsCodeFamily::profiles(user_data_dir.path()).is_empty());
+
+        let storage = user_data_dir.path().join("User/globalStorage");
+        fs::create_dir_all(&storage).unwrap();
+        fs::write(storage.join("storage.json"), "{}").unwrap();
+        assert!(VsCodeFamily::profiles(user_data_dir.path()).is_empty());
+    }
+
+    #[test]
+    fn reads_extension_version_from_profile() {
+        let profile_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            profile_dir.path().join("extensions.json"),
+            r#"[
+                {"identifier": {"id": "ms-python.python"}, "version": "2024.8.0"},
+                {"identifier": {"id": "wakatime.vscode-wakatime"}, "version": "25.0.0"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            profile_extension_version(profile_dir.path()),
+            Some("25.0.0".to_string())
+        );
+    }
+
+    #[test]
+    fn profile_without_extension_has_no_version() {
+        let profile_dir = tempfile::tempdir().unwrap();
+        assert_eq!(profile_extension_version(profile_dir.path()), None);
+
+        fs::write(
+            profile_dir.path().join("extensions.json"),
+            r#"[{"identifier": {"id": "ms-python.python"}, "version": "2024.8.0"}]"#,
+        )
+        .unwrap();
+        assert_eq!(profile_extension_version(profile_dir.path()), None);
+    }
+}
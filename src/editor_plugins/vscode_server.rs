@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use color_eyre::{Result, eyre::eyre};
+
+use super::EditorPlugin;
+use super::vscode::{EXTENSION_NAME, EXTENSION_PUBLISHER, extension_version};
+use crate::journal;
+use crate::wsl::Wsl;
+
+/// A VS Code server on this machine, driven by a desktop editor elsewhere. Extensions that
+/// track work in the workspace, like WakaTime, have to be installed here rather than on the
+/// desktop side.
+pub struct VsCodeServer {
+    name: String,
+    /// Holds the server builds, e.g. `~/.vscode-server`
+    builds_dir: PathBuf,
+    extensions_dir: PathBuf,
+}
+
+/// Per-user server dirs left by Remote-SSH, Remote-WSL and their Cursor/Windsurf equivalents
+const SERVER_DIRS: &[(&str, &str)] = &[
+    ("VS Code Server", ".vscode-server"),
+    ("Cursor Server", ".cursor-server"),
+    ("Windsurf Server", ".windsurf-server"),
+];
+
+/// Where devcontainer and Codespaces images keep their shared server builds
+const CONTAINER_BUILDS_DIR: &str = "/vscode/vscode-server";
+
+impl VsCodeServer {
+    fn in_home(name: &str, dir: &str) -> Option<Self> {
+        let data_dir = dirs::home_dir()?.join(dir);
+        Some(Self {
+            name: name.to_string(),
+            extensions_dir: data_dir.join("extensions"),
+            builds_dir: data_dir,
+        })
+    }
+
+    /// The Remote-WSL server, when running inside WSL
+    pub fn wsl() -> Option<Self> {
+        Wsl::detect()?;
+        Self::in_home("VS Code (WSL)", ".vscode-server")
+    }
+
+    /// Every kind of server this machine could be hosting, for `remote` mode
+    pub fn remote() -> Vec<Self> {
+        let mut servers: Vec<Self> = SERVER_DIRS
+            .iter()
+            .filter_map(|(name, dir)| Self::in_home(name, dir))
+            .collect();
+        // Containers keep builds in a shared dir but extensions in the user's usual one
+        if let Some(home) = dirs::home_dir() {
+            servers.push(Self {
+                name: "VS Code Server (container)".to_string(),
+                builds_dir: PathBuf::from(CONTAINER_BUILDS_DIR),
+                extensions_dir: home.join(".vscode-server/extensions"),
+            });
+        }
+        servers
+    }
+
+    /// The newest server build's own CLI, e.g. `bin/<commit>/bin/code-server`,
+    /// `cli/servers/Stable-<commit>/server/bin/code-server` or
+    /// `bin/linux-x64/<commit>/bin/code-server`
+    fn server_cli(&self) -> Option<PathBuf> {
+        let legacy = subdirs(&self.builds_dir.join("bin"));
+        // Container images add a platform level
+        let per_platform: Vec<PathBuf> = legacy.iter().flat_map(|dir| subdirs(dir)).collect();
+        let current = subdirs(&self.builds_dir.join("cli/servers"))
+            .into_iter()
+            .map(|build| build.join("server"));
+
+        legacy
+            .into_iter()
+            .chain(per_platform)
+            .chain(current)
+            .filter_map(|build| build_cli(&build))
+            .max_by_key(|cli| fs::metadata(cli).and_then(|m| m.modified()).ok())
+    }
+
+    fn cli_command(&self, action: &str) -> Result<Command> {
+        let cli = self.server_cli().ok_or_else(|| {
+            eyre!(
+                "No server build found in {}. Connect to this machine from your editor once, then try again.",
+                self.builds_dir.display()
+            )
+        })?;
+
+        let mut cmd = Command::new(cli);
+        cmd.arg("--extensions-dir")
+            .arg(&self.extensions_dir)
+            .arg(action)
+            .arg(format!("{EXTENSION_PUBLISHER}.{EXTENSION_NAME}"));
+        Ok(cmd)
+    }
+
+    fn run_cli(&self, action: &str) -> Result<()> {
+        let mut cmd = self.cli_command(action)?;
+        let status = cmd
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_err(|e| eyre!("Failed to execute {:?}: {}", cmd.get_program(), e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "{} failed for {}. Exit code: {:?}",
+                action,
+                self.name,
+                status.code()
+            ))
+        }
+    }
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The `bin/*-server` executable of a single server build
+fn build_cli(build: &Path) -> Option<PathBuf> {
+    fs::read_dir(build.join("bin"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().ends_with("-server"))
+        })
+}
+
+impl EditorPlugin for VsCodeServer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn is_installed(&self) -> bool {
+        self.server_cli().is_some()
+    }
+
+    fn install_path(&self) -> Option<PathBuf> {
+        Some(self.extensions_dir.clone())
+    }
+
+    fn plugin_version(&self) -> Option<String> {
+        extension_version(&self.extensions_dir)
+    }
+
+    fn install(&self) -> Result<()> {
+        // Reinstalling an extension that was already there isn't something to undo
+        if self.plugin_version().is_none() {
+            journal::record_command(&self.cli_command("--uninstall-extension")?)?;
+        }
+        self.run_cli("--install-extension")
+    }
+
+    fn uninstall(&self) -> Result<()> {
+        self.run_cli("--uninstall-extension")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_with(cli: &str) -> (tempfile::TempDir, VsCodeServer, PathBuf) {
+        let builds_dir = tempfile::tempdir().unwrap();
+        let cli = builds_dir.path().join(cli);
+        fs::create_dir_all(cli.parent().unwrap()).unwrap();
+        fs::write(&cli, "").unwrap();
+
+        let server = VsCodeServer {
+            name: "VS Code Server".to_string(),
+            builds_dir: builds_dir.path().to_path_buf(),
+            extensions_dir: builds_dir.path().join("extensions"),
+        };
+        (builds_dir, server, cli)
+    }
+
+    #[test]
+    fn finds_legacy_layout() {
+        let (_dir, server, cli) = server_with("bin/abc123/bin/code-server");
+        assert_eq!(server.server_cli(), Some(cli));
+    }
+
+    #[test]
+    fn finds_cli_servers_layout() {
+        let (_dir, server, cli) = server_with("cli/servers/Stable-abc123/server/bin/code-server");
+        assert_eq!(server.server_cli(), Some(cli));
+    }
+
+    #[test]
+    fn finds_container_layout() {
+        let (_dir, server, cli) = server_with("bin/linux-x64/abc123/bin/code-server");
+        assert_eq!(server.server_cli(), Some(cli));
+    }
+
+    #[test]
+    fn ignores_extension_binaries() {
+        let (_dir, server, _) = server_with("extensions/some.ext-1.0.0/bin/language-server");
+        assert_eq!(server.server_cli(), None);
+    }
+}
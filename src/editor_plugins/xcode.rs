@@ -1,17 +1,22 @@
+#[cfg(target_os = "macos")]
 use std::fs;
+#[cfg(target_os = "macos")]
 use std::path::PathBuf;
+#[cfg(target_os = "macos")]
 use std::process::Command;
 
 use color_eyre::{Result, eyre::eyre};
 
 use super::EditorPlugin;
 
+#[cfg(target_os = "macos")]
 const DOWNLOAD_URL: &str =
     "https://github.com/wakatime/macos-wakatime/releases/latest/download/macos-wakatime.zip";
 
 pub struct Xcode;
 
 impl Xcode {
+    #[cfg(target_os = "macos")]
     fn app_path() -> PathBuf {
         PathBuf::from("/Applications/WakaTime.app")
     }
@@ -38,10 +43,22 @@ impl EditorPlugin for Xcode {
         }
     }
 
+    fn install_path(&self) -> Option<std::path::PathBuf> {
+        #[cfg(target_os = "macos")]
+        {
+            Some(Self::app_path())
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        {
+            None
+        }
+    }
+
     fn install(&self) -> Result<()> {
         #[cfg(not(target_os = "macos"))]
         {
-            return Err(eyre!("Xcode is only supported on macOS"));
+            Err(eyre!("Xcode is only supported on macOS"))
         }
 
         #[cfg(target_os = "macos")]
@@ -94,6 +111,7 @@ impl EditorPlugin for Xcode {
                 return Err(eyre!("WakaTime.app not found in downloaded archive"));
             }
 
+            crate::journal::record_write(&Self::app_path())?;
             let status = Command::new("cp")
                 .args([
                     "-R",
@@ -1,54 +1,172 @@
-use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use color_eyre::{Result, eyre::eyre};
 use colored::Colorize;
-use jsonc_parser::{ParseOptions, cst::CstRootNode, json};
+use jsonc_parser::json;
 
 use super::EditorPlugin;
-use super::utils::is_process_running;
+use super::sandbox::Sandbox;
+use super::utils::{edit_json_settings, is_process_running};
 
-pub struct Zed;
+const FLATPAK_ID: &str = "dev.zed.Zed";
+
+/// What advanced setup passes to the WakaTime extension through
+/// `lsp.wakatime.initialization_options`, for setups where the extension can't rely on
+/// `~/.wakatime.cfg` (e.g. a non-default `WAKATIME_HOME`)
+#[derive(Clone, Default)]
+pub struct ZedSettings {
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+}
+
+impl ZedSettings {
+    fn entries(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("api-url", self.api_url.as_deref()),
+            ("api-key", self.api_key.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect()
+    }
+}
+
+/// Zed release channels. They install side by side as separate apps but share one config and
+/// data dir.
+#[derive(Clone, Copy, PartialEq)]
+enum ZedChannel {
+    Stable,
+    Preview,
+    Nightly,
+    Dev,
+}
+
+impl ZedChannel {
+    const ALL: [Self; 4] = [Self::Stable, Self::Preview, Self::Nightly, Self::Dev];
+
+    /// Also the macOS app and Windows install folder name
+    fn app_name(self) -> &'static str {
+        match self {
+            Self::Stable => "Zed",
+            Self::Preview => "Zed Preview",
+            Self::Nightly => "Zed Nightly",
+            Self::Dev => "Zed Dev",
+        }
+    }
+
+    /// Dir under `~/.local` the official Linux installer unpacks this channel into
+    #[cfg(target_os = "linux")]
+    fn linux_app_dir(self) -> &'static str {
+        match self {
+            Self::Stable => "zed.app",
+            Self::Preview => "zed-preview.app",
+            Self::Nightly => "zed-nightly.app",
+            Self::Dev => "zed-dev.app",
+        }
+    }
+
+    /// The channel a Linux `zed` binary belongs to, going by the app dir it resolves into.
+    /// Distro packages (`zeditor`, `/usr/bin/zed`) are always Stable.
+    #[cfg(target_os = "linux")]
+    fn of_linux_binary(path: &Path) -> Self {
+        let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        Self::ALL
+            .into_iter()
+            .find(|channel| {
+                resolved
+                    .components()
+                    .any(|c| c.as_os_str() == channel.linux_app_dir())
+            })
+            .unwrap_or(Self::Stable)
+    }
+}
+
+pub struct Zed {
+    channel: ZedChannel,
+    settings: ZedSettings,
+}
 
 impl Zed {
-    fn has_url_handler() -> bool {
+    pub fn channels(settings: &ZedSettings) -> Vec<Self> {
+        ZedChannel::ALL
+            .into_iter()
+            .map(|channel| Self {
+                channel,
+                settings: settings.clone(),
+            })
+            .collect()
+    }
+
+    fn flatpak(&self) -> Option<Sandbox> {
+        if self.channel != ZedChannel::Stable {
+            return None;
+        }
+        Sandbox::installed(&[FLATPAK_ID.to_string()], &[]).pop()
+    }
+
+    fn has_app(&self) -> bool {
         #[cfg(target_os = "macos")]
         {
             Command::new("/usr/bin/open")
-                .args(["-Ra", "zed"])
+                .args(["-Ra", self.channel.app_name()])
                 .output()
                 .is_ok_and(|o| o.status.success())
         }
 
         #[cfg(target_os = "linux")]
         {
-            if let Ok(o) = Command::new("xdg-mime")
-                .args(["query", "default", "x-scheme-handler/zed"])
-                .output()
+            if self.channel == ZedChannel::Stable
+                && let Ok(o) = Command::new("xdg-mime")
+                    .args(["query", "default", "x-scheme-handler/zed"])
+                    .output()
+                && o.status.success()
+                && !o.stdout.is_empty()
             {
-                if o.status.success() && !o.stdout.is_empty() {
-                    return true;
-                }
+                return true;
+            }
+
+            let home = dirs::home_dir().unwrap_or_default();
+            if home
+                .join(".local")
+                .join(self.channel.linux_app_dir())
+                .join("bin/zed")
+                .exists()
+            {
+                return true;
             }
-            [
+
+            // Distro packages name the binary `zeditor` to avoid clashing with other `zed`s
+            let mut binaries: Vec<PathBuf> = ["zed", "zeditor", "zedit", "zed-editor"]
+                .iter()
+                .filter_map(|name| which::which(name).ok())
+                .collect();
+            binaries.extend([
                 PathBuf::from("/usr/bin/zed"),
                 PathBuf::from("/usr/bin/zeditor"),
                 PathBuf::from("/usr/local/bin/zed"),
-                dirs::home_dir()
-                    .map(|h| h.join(".local/bin/zed"))
-                    .unwrap_or_default(),
-            ]
-            .iter()
-            .any(|p| p.exists())
+                home.join(".local/bin/zed"),
+            ]);
+            binaries
+                .iter()
+                .any(|p| p.exists() && ZedChannel::of_linux_binary(p) == self.channel)
         }
 
         #[cfg(target_os = "windows")]
         {
-            Command::new("reg")
-                .args(["query", r"HKEY_CLASSES_ROOT\zed"])
-                .output()
-                .is_ok_and(|o| o.status.success())
+            let folder = std::env::var("LOCALAPPDATA")
+                .map(|d| {
+                    PathBuf::from(d)
+                        .join("Programs")
+                        .join(self.channel.app_name())
+                })
+                .is_ok_and(|d| d.exists());
+            folder
+                || (self.channel == ZedChannel::Stable
+                    && Command::new("reg")
+                        .args(["query", r"HKEY_CLASSES_ROOT\zed"])
+                        .output()
+                        .is_ok_and(|o| o.status.success()))
         }
 
         #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
@@ -82,70 +200,245 @@ impl Zed {
         }
     }
 
-    fn add_extension_to_settings(settings_path: &PathBuf) -> Result<()> {
-        let content = if settings_path.exists() {
-            let s = fs::read_to_string(settings_path)
-                .map_err(|e| eyre!("Failed to read {}: {}", settings_path.display(), e))?;
-            if s.trim().is_empty() {
-                String::from("{}")
-            } else {
-                s
-            }
-        } else {
-            String::from("{}")
-        };
-
-        let root = CstRootNode::parse(&content, &ParseOptions::default())
-            .map_err(|e| eyre!("Invalid {}: {}", settings_path.display(), e))?;
+    /// Where Zed unpacks extensions once it has installed them
+    fn installed_extensions_dir(sandbox: Option<&Sandbox>) -> Option<PathBuf> {
+        if let Some(sandbox) = sandbox {
+            return sandbox
+                .data_dir()
+                .map(|d| d.join("zed/extensions/installed"));
+        }
 
-        let root_obj = root
-            .object_value_or_create()
-            .ok_or_else(|| eyre!("{} root must be an object", settings_path.display()))?;
+        #[cfg(target_os = "macos")]
+        {
+            dirs::home_dir().map(|h| h.join("Library/Application Support/Zed/extensions/installed"))
+        }
 
-        let extensions = root_obj
-            .object_value_or_create("auto_install_extensions")
-            .ok_or_else(|| eyre!("auto_install_extensions must be an object"))?;
+        #[cfg(target_os = "linux")]
+        {
+            dirs::data_dir().map(|d| d.join("zed/extensions/installed"))
+        }
 
-        match extensions.get("wakatime") {
-            None => {
-                extensions.append("wakatime", json!(true));
-            }
-            Some(prop) => {
-                prop.set_value(json!(true));
-            }
+        #[cfg(target_os = "windows")]
+        {
+            dirs::data_local_dir().map(|d| d.join("Zed/extensions/installed"))
         }
 
-        if let Some(parent) = settings_path.parent() {
-            fs::create_dir_all(parent)?;
+        #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+        {
+            None
         }
-        fs::write(settings_path, root.to_string())
-            .map_err(|e| eyre!("Failed to write {}: {}", settings_path.display(), e))?;
+    }
 
-        Ok(())
+    /// The extension's folder once Zed has actually installed it, which only happens once it
+    /// restarts after `auto_install_extensions` was added
+    fn installed_extension(&self) -> Option<PathBuf> {
+        let flatpak = self.flatpak();
+        [
+            Self::installed_extensions_dir(None),
+            Self::installed_extensions_dir(flatpak.as_ref()),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join("wakatime"))
+        .find(|dir| dir.is_dir())
+    }
+
+    /// The host `settings.json` plus the Flatpak one, skipping the host one if only the
+    /// Flatpak is in use
+    fn settings_paths(&self) -> Vec<PathBuf> {
+        let flatpak = self
+            .flatpak()
+            .and_then(|f| f.config_dir())
+            .map(|c| c.join("zed"));
+        let host = Self::config_dir().filter(|d| flatpak.is_none() || d.exists());
+        host.into_iter()
+            .chain(flatpak)
+            .map(|d| d.join("settings.json"))
+            .collect()
+    }
+
+    pub(super) fn add_extension_to_settings(&self, settings_path: &Path) -> Result<()> {
+        edit_json_settings(settings_path, |root_obj| {
+            let extensions = root_obj
+                .object_value_or_create("auto_install_extensions")
+                .ok_or_else(|| eyre!("auto_install_extensions must be an object"))?;
+
+            match extensions.get("wakatime") {
+                None => {
+                    extensions.append("wakatime", json!(true));
+                }
+                Some(prop) => {
+                    prop.set_value(json!(true));
+                }
+            }
+
+            let entries = self.settings.entries();
+            if entries.is_empty() {
+                return Ok(());
+            }
+            let options = root_obj
+                .object_value_or_create("lsp")
+                .and_then(|lsp| lsp.object_value_or_create("wakatime"))
+                .and_then(|wakatime| wakatime.object_value_or_create("initialization_options"))
+                .ok_or_else(|| eyre!("lsp.wakatime.initialization_options must be an object"))?;
+            for (key, value) in entries {
+                match options.get(key) {
+                    None => {
+                        options.append(key, json!(value));
+                    }
+                    Some(prop) => prop.set_value(json!(value)),
+                }
+            }
+
+            Ok(())
+        })
     }
 }
 
+/// The top-level `version` of an `extension.toml`
+fn manifest_version(manifest: &str) -> Option<String> {
+    manifest
+        .lines()
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "version").then(|| value.trim().trim_matches('"').to_string())
+        })
+}
+
 impl EditorPlugin for Zed {
     fn name(&self) -> String {
-        "Zed".to_string()
+        self.channel.app_name().to_string()
     }
 
     fn is_installed(&self) -> bool {
-        Self::has_url_handler()
+        self.has_app() || self.flatpak().is_some()
+    }
+
+    fn install_path(&self) -> Option<PathBuf> {
+        self.settings_paths().into_iter().next()
+    }
+
+    fn plugin_version(&self) -> Option<String> {
+        let manifest = std::fs::read_to_string(self.installed_extension()?.join("extension.toml"));
+        manifest_version(&manifest.ok()?)
     }
 
     fn install(&self) -> Result<()> {
-        if is_process_running("zed") {
-            eprintln!(
-                "{}",
-                "Warning: Zed appears to be running - you'll need to restart the editor to finalize installation.".yellow()
-            );
+        let settings_paths = self.settings_paths();
+        if settings_paths.is_empty() {
+            return Err(eyre!("Could not determine Zed config directory"));
+        }
+
+        settings_paths
+            .iter()
+            .try_for_each(|path| self.add_extension_to_settings(path))?;
+
+        if self.installed_extension().is_none() {
+            let note = if is_process_running("zed") {
+                format!(
+                    "Note: restart {} to finish installing the WakaTime extension.",
+                    self.name()
+                )
+            } else {
+                format!(
+                    "Note: {} will install the WakaTime extension the next time it starts.",
+                    self.name()
+                )
+            };
+            eprintln!("{}", note.yellow());
         }
 
-        let settings_path = Self::config_dir()
-            .ok_or_else(|| eyre!("Could not determine Zed config directory"))?
-            .join("settings.json");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_target_per_channel() {
+        let names: Vec<String> = Zed::channels(&ZedSettings::default())
+            .iter()
+            .map(Zed::name)
+            .collect();
+        assert_eq!(names, ["Zed", "Zed Preview", "Zed Nightly", "Zed Dev"]);
+    }
+
+    fn zed(settings: ZedSettings) -> Zed {
+        Zed::channels(&settings).remove(0)
+    }
+
+    #[test]
+    fn passes_settings_as_initialization_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(
+            &path,
+            r#"{"lsp": {"wakatime": {"initialization_options": {"api-url": "https://old"}}}}"#,
+        )
+        .unwrap();
+
+        zed(ZedSettings {
+            api_url: Some("https://hackatime.hackclub.com/api/hackatime/v1".to_string()),
+            api_key: None,
+        })
+        .add_extension_to_settings(&path)
+        .unwrap();
+
+        let settings: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            settings["lsp"]["wakatime"]["initialization_options"],
+            serde_json::json!({"api-url": "https://hackatime.hackclub.com/api/hackatime/v1"})
+        );
+        assert_eq!(settings["auto_install_extensions"]["wakatime"], true);
+    }
+
+    #[test]
+    fn leaves_lsp_settings_alone_without_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        zed(ZedSettings::default())
+            .add_extension_to_settings(&path)
+            .unwrap();
+
+        let settings = std::fs::read_to_string(&path).unwrap();
+        assert!(!settings.contains("lsp"));
+    }
+
+    #[test]
+    fn reads_extension_manifest_version() {
+        let manifest = r#"id = "wakatime"
+name = "WakaTime"
+version = "0.1.9"
+schema_version = 1
+
+[language_servers.wakatime]
+name = "WakaTime"
+version = "2.0.0"
+"#;
+        assert_eq!(manifest_version(manifest), Some("0.1.9".to_string()));
+        assert_eq!(
+            manifest_version("[language_servers.wakatime]\nversion = \"2.0.0\"\n"),
+            None
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tells_channel_from_linux_app_dir() {
+        let home = tempfile::tempdir().unwrap();
+        let preview = home.path().join(".local/zed-preview.app/bin/zed");
+        std::fs::create_dir_all(preview.parent().unwrap()).unwrap();
+        std::fs::write(&preview, "").unwrap();
+        let link = home.path().join(".local/bin/zed");
+        std::fs::create_dir_all(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(&preview, &link).unwrap();
 
-        Self::add_extension_to_settings(&settings_path)
+        assert!(ZedChannel::of_linux_binary(&preview) == ZedChannel::Preview);
+        assert!(ZedChannel::of_linux_binary(&link) == ZedChannel::Preview);
+        assert!(ZedChannel::of_linux_binary(Path::new("/usr/bin/zeditor")) == ZedChannel::Stable);
     }
 }
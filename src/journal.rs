@@ -0,0 +1,570 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::{
+    Result,
+    eyre::{ContextCompat, eyre},
+};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Something a setup run did, recorded before doing it together with how to undo it
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Change {
+    /// `path`, a file or directory, was written. `backup` holds whatever was there before.
+    Write {
+        path: PathBuf,
+        backup: Option<PathBuf>,
+        /// Hash of the file when the run finished, so a replay can tell if it was edited since
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        written: Option<String>,
+    },
+    /// A command was run, and running `undo` reverses it
+    Command { undo: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Journal {
+    started: u64,
+    finished: bool,
+    changes: Vec<Change>,
+}
+
+/// A setup run's journal and the directory it lives in, next to its backups
+pub struct Run {
+    dir: PathBuf,
+    journal: Journal,
+}
+
+/// Set by Ctrl-C. The run is undone by the main thread as it unwinds, so nothing it is in the
+/// middle of can slip past the journal.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+fn check_interrupted() -> Result<()> {
+    if interrupted() {
+        Err(eyre!("Interrupted"))
+    } else {
+        Ok(())
+    }
+}
+
+/// The run in progress. Global so editor plugins can record into it wherever they write.
+#[cfg(not(test))]
+fn current() -> MutexGuard<'static, Option<Run>> {
+    static CURRENT: Mutex<Option<Run>> = Mutex::new(None);
+    // A panic while recording doesn't make the journal itself any less usable
+    CURRENT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// One run per test thread, so tests that write files in parallel don't record into each
+/// other's runs
+#[cfg(test)]
+fn current() -> MutexGuard<'static, Option<Run>> {
+    thread_local! {
+        static CURRENT: &'static Mutex<Option<Run>> = Box::leak(Box::new(Mutex::new(None)));
+    }
+    CURRENT.with(|current| current.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn runs_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".wakatime/hackatime-setup/runs"))
+}
+
+/// Starts journaling a setup run. Until the returned transaction is committed, an error that
+/// drops it or Ctrl-C undoes everything the run did. After Ctrl-C, recording and committing
+/// fail so the caller unwinds to the transaction's drop.
+pub fn begin() -> Result<Transaction> {
+    let transaction = begin_in(&runs_dir().wrap_err("Could not find home directory")?)?;
+
+    ctrlc::set_handler(|| {
+        // An interrupted prompt leaves the cursor hidden
+        let _ = dialoguer::console::Term::stderr().show_cursor();
+        // Once the run is committed there's nothing left to undo
+        if current().is_none() {
+            std::process::exit(130);
+        }
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    })
+    .map_err(|e| eyre!("Failed to set up Ctrl-C handling: {}", e))?;
+
+    Ok(transaction)
+}
+
+/// Starts journaling a run into a new dir under `runs_dir`
+fn begin_in(runs_dir: &Path) -> Result<Transaction> {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let dir = runs_dir.join(started.to_string());
+    fs::create_dir_all(dir.join("backups"))
+        .map_err(|e| eyre!("Failed to create {}: {}", dir.display(), e))?;
+
+    let run = Run {
+        dir,
+        journal: Journal {
+            started,
+            finished: false,
+            changes: Vec::new(),
+        },
+    };
+    run.save()?;
+    *current() = Some(run);
+
+    Ok(Transaction { committed: false })
+}
+
+/// Records that `path` is about to be written, backing up what's there now. Does nothing
+/// outside a setup run, e.g. during `uninstall`, or if this run already recorded `path`.
+pub fn record_write(path: &Path) -> Result<()> {
+    let mut current = current();
+    let Some(run) = current.as_mut() else {
+        return Ok(());
+    };
+    check_interrupted()?;
+    let recorded = run
+        .journal
+        .changes
+        .iter()
+        .any(|change| matches!(change, Change::Write { path: p, .. } if p == path));
+    if recorded {
+        return Ok(());
+    }
+
+    let backup = if path.symlink_metadata().is_ok() {
+        let backup = run
+            .dir
+            .join("backups")
+            .join(run.journal.changes.len().to_string());
+        copy_all(path, &backup)
+            .map_err(|e| eyre!("Failed to back up {}: {}", path.display(), e))?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    run.journal.changes.push(Change::Write {
+        path: path.to_path_buf(),
+        backup,
+        written: None,
+    });
+    run.save()
+}
+
+/// Records a command that undoes the one about to be run
+pub fn record_command(undo: &Command) -> Result<()> {
+    let mut current = current();
+    let Some(run) = current.as_mut() else {
+        return Ok(());
+    };
+    check_interrupted()?;
+
+    let undo = std::iter::once(undo.get_program())
+        .chain(undo.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    run.journal.changes.push(Change::Command { undo });
+    run.save()
+}
+
+/// Marks the current point in the run, to roll back to if a later step fails
+pub fn checkpoint() -> usize {
+    current()
+        .as_ref()
+        .map_or(0, |run| run.journal.changes.len())
+}
+
+/// Undoes everything recorded since `checkpoint`
+pub fn rollback_to(checkpoint: usize) -> Result<()> {
+    let mut current = current();
+    let Some(run) = current.as_mut() else {
+        return Ok(());
+    };
+
+    let checkpoint = checkpoint.min(run.journal.changes.len());
+    let undone = run.journal.changes.split_off(checkpoint);
+    let result = undo_all(&undone, false);
+    run.save()?;
+    result
+}
+
+/// Undoes everything the current run did and forgets it. The journal stays on disk if
+/// anything couldn't be undone, so `uninstall` can have another go.
+fn abort() -> Result<()> {
+    let Some(run) = current().take() else {
+        return Ok(());
+    };
+
+    undo_all(&run.journal.changes, false)?;
+    fs::remove_dir_all(&run.dir).map_err(|e| eyre!("Failed to remove {}: {}", run.dir.display(), e))
+}
+
+/// The run in progress, rolled back when dropped without being committed
+pub struct Transaction {
+    committed: bool,
+}
+
+impl Transaction {
+    /// Keeps everything the run did, recording what each file ended up as. Runs that changed
+    /// nothing aren't kept. Fails, undoing the run, if it was interrupted.
+    pub fn commit(mut self) -> Result<()> {
+        check_interrupted()?;
+        self.committed = true;
+        let Some(mut run) = current().take() else {
+            return Ok(());
+        };
+
+        if run.journal.changes.is_empty() {
+            return fs::remove_dir_all(&run.dir)
+                .map_err(|e| eyre!("Failed to remove {}: {}", run.dir.display(), e));
+        }
+
+        for change in &mut run.journal.changes {
+            if let Change::Write { path, written, .. } = change {
+                *written = hash(path);
+            }
+        }
+        run.journal.finished = true;
+        run.save()
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        if interrupted() {
+            eprintln!("\n{}", "Interrupted, undoing changes...".yellow());
+        } else {
+            eprintln!("{}", "Setup failed, undoing changes...".yellow());
+        }
+        if let Err(e) = abort() {
+            eprintln!("{} {}", "Warning:".yellow(), e);
+        }
+        if interrupted() {
+            std::process::exit(130);
+        }
+    }
+}
+
+impl Run {
+    fn save(&self) -> Result<()> {
+        let path = self.dir.join("journal.json");
+        let json = serde_json::to_string_pretty(&self.journal)?;
+        fs::write(&path, json).map_err(|e| eyre!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Runs left on disk by earlier setups, newest first
+    pub fn recorded() -> Vec<Self> {
+        runs_dir()
+            .map(|dir| Self::recorded_in(&dir))
+            .unwrap_or_default()
+    }
+
+    fn recorded_in(runs_dir: &Path) -> Vec<Self> {
+        let mut runs: Vec<Self> = fs::read_dir(runs_dir)
+            .into_iter()
+            .flat_map(|entries| entries.flatten())
+            .filter_map(|entry| {
+                let dir = entry.path();
+                let json = fs::read_to_string(dir.join("journal.json")).ok()?;
+                let journal = serde_json::from_str(&json).ok()?;
+                Some(Self { dir, journal })
+            })
+            .collect();
+        runs.sort_by_key(|run| std::cmp::Reverse(run.journal.started));
+        runs
+    }
+
+    pub fn change_count(&self) -> usize {
+        self.journal.changes.len()
+    }
+
+    /// Undoes everything this run did, except files edited since, then forgets it
+    pub fn replay(self) -> Result<()> {
+        let result = undo_all(&self.journal.changes, true);
+        fs::remove_dir_all(&self.dir)
+            .map_err(|e| eyre!("Failed to remove {}: {}", self.dir.display(), e))?;
+        result
+    }
+}
+
+/// Undoes `changes` newest first, carrying on past failures so as much as possible is undone
+fn undo_all(changes: &[Change], keep_edited: bool) -> Result<()> {
+    let errors: Vec<String> = changes
+        .iter()
+        .rev()
+        .filter_map(|change| undo(change, keep_edited).err())
+        .map(|e| e.to_string())
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!("{}", errors.join("\n")))
+    }
+}
+
+fn undo(change: &Change, keep_edited: bool) -> Result<()> {
+    match change {
+        Change::Write {
+            path,
+            backup,
+            written,
+        } => {
+            if keep_edited && written.is_some() && hash(path) != *written {
+                return Err(eyre!(
+                    "{} was changed after setup, leaving it as is",
+                    path.display()
+                ));
+            }
+
+            remove_all(path).map_err(|e| eyre!("Failed to remove {}: {}", path.display(), e))?;
+            if let Some(backup) = backup {
+                copy_all(backup, path)
+                    .map_err(|e| eyre!("Failed to restore {}: {}", path.display(), e))?;
+            }
+            Ok(())
+        }
+        Change::Command { undo } => {
+            let (program, args) = undo.split_first().wrap_err("Empty undo command")?;
+            let status = Command::new(program)
+                .args(args)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map_err(|e| eyre!("Failed to execute {}: {}", program, e))?;
+
+            if status.success() {
+                Ok(())
+            } else {
+                Err(eyre!(
+                    "`{}` failed. Exit code: {:?}",
+                    undo.join(" "),
+                    status.code()
+                ))
+            }
+        }
+    }
+}
+
+fn hash(path: &Path) -> Option<String> {
+    let content = fs::read(path).ok()?;
+    Some(format!("{:x}", Sha256::digest(content)))
+}
+
+fn remove_all(path: &Path) -> std::io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Copies a file, or a directory recursively. Symlinks are copied as links, so restoring a
+/// backup doesn't replace a dotfile manager's link with a plain file.
+fn copy_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    #[cfg(unix)]
+    if from.symlink_metadata()?.file_type().is_symlink() {
+        return std::os::unix::fs::symlink(fs::read_link(from)?, to);
+    }
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_all(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A run journaled into its own temp dir, with `files` as a scratch dir next to it
+    fn begin_test() -> (tempfile::TempDir, Transaction) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("files")).unwrap();
+        let transaction = begin_in(&dir.path().join("runs")).unwrap();
+        (dir, transaction)
+    }
+
+    fn write(path: &Path, content: &str) {
+        record_write(path).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn rollback_restores_original() {
+        let (dir, transaction) = begin_test();
+        let path = dir.path().join("files/settings.json");
+        fs::write(&path, "original").unwrap();
+
+        let checkpoint = checkpoint();
+        write(&path, "edited");
+        rollback_to(checkpoint).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+        transaction.commit().unwrap();
+        // Nothing is left to keep, so neither is the run
+        assert!(Run::recorded_in(&dir.path().join("runs")).is_empty());
+    }
+
+    #[test]
+    fn rollback_removes_new_files() {
+        let (dir, transaction) = begin_test();
+        let kept = dir.path().join("files/kept");
+        let new_file = dir.path().join("files/settings.json");
+        let new_dir = dir.path().join("files/WakaTime");
+
+        write(&kept, "kept");
+        let checkpoint = checkpoint();
+        write(&new_file, "new");
+        record_write(&new_dir).unwrap();
+        fs::create_dir_all(new_dir.join("lib")).unwrap();
+        rollback_to(checkpoint).unwrap();
+
+        assert!(!new_file.exists());
+        assert!(!new_dir.exists());
+        assert!(kept.exists());
+        transaction.commit().unwrap();
+        assert_eq!(
+            Run::recorded_in(&dir.path().join("runs"))[0].change_count(),
+            1
+        );
+    }
+
+    #[test]
+    fn records_each_path_once() {
+        let (dir, transaction) = begin_test();
+        let path = dir.path().join("files/settings.json");
+        fs::write(&path, "original").unwrap();
+
+        write(&path, "first");
+        write(&path, "second");
+        assert_eq!(checkpoint(), 1);
+
+        drop(transaction);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+    }
+
+    #[test]
+    fn drop_undoes_uncommitted_run() {
+        let (dir, transaction) = begin_test();
+        let edited = dir.path().join("files/edited");
+        let created = dir.path().join("files/created");
+        fs::write(&edited, "original").unwrap();
+
+        write(&edited, "edited");
+        write(&created, "created");
+        drop(transaction);
+
+        assert_eq!(fs::read_to_string(&edited).unwrap(), "original");
+        assert!(!created.exists());
+        assert!(current().is_none());
+        assert!(Run::recorded_in(&dir.path().join("runs")).is_empty());
+    }
+
+    #[test]
+    fn replay_keeps_files_edited_after_setup() {
+        let (dir, transaction) = begin_test();
+        let edited = dir.path().join("files/edited");
+        let untouched = dir.path().join("files/untouched");
+        fs::write(&edited, "original").unwrap();
+        fs::write(&untouched, "original").unwrap();
+
+        write(&edited, "from setup");
+        write(&untouched, "from setup");
+        transaction.commit().unwrap();
+        assert!(current().is_none());
+        // Recording outside a run does nothing
+        record_write(&untouched).unwrap();
+        assert_eq!(checkpoint(), 0);
+
+        fs::write(&edited, "by hand").unwrap();
+        let mut runs = Run::recorded_in(&dir.path().join("runs"));
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].change_count(), 2);
+
+        let error = runs.remove(0).replay().unwrap_err().to_string();
+        assert!(error.contains("was changed after setup"), "{error}");
+        assert_eq!(fs::read_to_string(&edited).unwrap(), "by hand");
+        assert_eq!(fs::read_to_string(&untouched).unwrap(), "original");
+        assert!(Run::recorded_in(&dir.path().join("runs")).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rollback_runs_undo_commands() {
+        let (dir, transaction) = begin_test();
+        let marker = dir.path().join("files/undone");
+
+        let checkpoint = checkpoint();
+        let mut undo = Command::new("touch");
+        undo.arg(&marker);
+        record_command(&undo).unwrap();
+        rollback_to(checkpoint).unwrap();
+
+        assert!(marker.exists());
+        transaction.commit().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_rc_file_stays_a_link() {
+        let (dir, transaction) = begin_test();
+        let target = dir.path().join("files/dotfiles-bashrc");
+        let link = dir.path().join("files/.bashrc");
+        fs::write(&target, "alias ll='ls -l'\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        // Writers go through the link, so the file it points to is what gets recorded
+        write(&fs::canonicalize(&link).unwrap(), "echo hi\n");
+        // A link recorded itself is backed up as a link
+        record_write(&link).unwrap();
+        drop(transaction);
+
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "alias ll='ls -l'\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_symlinks_as_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        fs::create_dir_all(from.join("lib")).unwrap();
+        fs::write(from.join("lib/plugin.jar"), "jar").unwrap();
+        std::os::unix::fs::symlink("lib/plugin.jar", from.join("current.jar")).unwrap();
+
+        let to = dir.path().join("backups/0");
+        copy_all(&from, &to).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(to.join("lib/plugin.jar")).unwrap(),
+            "jar"
+        );
+        assert_eq!(
+            fs::read_link(to.join("current.jar")).unwrap(),
+            Path::new("lib/plugin.jar")
+        );
+    }
+}
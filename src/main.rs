@@ -1,6 +1,7 @@
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use color_eyre::{Result, eyre::ContextCompat};
 use colored::Colorize;
 use dialoguer::{Confirm, MultiSelect, Select, theme::ColorfulTheme};
@@ -13,36 +14,117 @@ use inkjet::{
 };
 use rand::Rng;
 use rayon::prelude::*;
-use reqwest::blocking::Client;
-use serde::Serialize;
 use termcolor::{ColorChoice, StandardStream};
 use uuid::Uuid;
 
-use crate::editor_plugins::EditorPlugin;
+use crate::api::{HackatimeClient, Heartbeat, KeyRejected};
+use crate::editor_plugins::{
+    DEFAULT_VSIX_GALLERY_URL, EditorPlugin, SetupOptions, VsCodeServer, VsCodeSettings, ZedSettings,
+};
+use crate::state::State;
 
+mod api;
 mod editor_plugins;
+mod journal;
+mod state;
+mod watch;
+mod wsl;
 
 const DEFAULT_API_URL: &str = "https://hackatime.hackclub.com/api/hackatime/v1";
+const INSTALL_SCRIPT_URL: &str =
+    "https://raw.githubusercontent.com/hackclub/hackatime-setup/main/install.sh";
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Cli {
     /// The API key to use
-    #[arg(short, long)]
-    key: String,
+    #[arg(short, long, required = true)]
+    key: Option<String>,
 
-    /// The API URL to use
+    /// The API URL to use, or `hackatime`, `wakatime` or `wakapi` for those servers
     #[arg(long, default_value = DEFAULT_API_URL)]
     api_url: String,
+
+    /// HTTP(S) proxy for reaching the API, also written to the config for your editors
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// User data directory for a VS Code-family editor with a portable or custom layout
+    #[arg(long, value_name = "DIR")]
+    user_data_dir: Option<PathBuf>,
+
+    /// Extensions directory for a VS Code-family editor with a portable or custom layout
+    #[arg(long, value_name = "DIR")]
+    extensions_dir: Option<PathBuf>,
+
+    /// The VS Code-family editor `--user-data-dir` and `--extensions-dir` are for, e.g. "Cursor"
+    #[arg(long, value_name = "NAME", default_value = "VS Code")]
+    vscode_family: String,
+
+    /// Open VSX-compatible gallery to fetch the VS Code extension from if the editor's own
+    /// marketplace fails
+    #[arg(long, value_name = "URL", default_value = DEFAULT_VSIX_GALLERY_URL)]
+    gallery_url: String,
+
+    /// Local WakaTime `.vsix` to install if the editor's own marketplace fails
+    #[arg(long, value_name = "FILE")]
+    vsix: Option<PathBuf>,
+
+    /// Install the JetBrains plugin by unpacking it into the IDE's plugins directory instead
+    /// of running the IDE's CLI
+    #[arg(long)]
+    jetbrains_unpack: bool,
+
+    /// Local WakaTime JetBrains plugin zip to unpack instead of downloading one
+    #[arg(long, value_name = "FILE")]
+    jetbrains_plugin_zip: Option<PathBuf>,
+
+    /// JSON file of extra editor families, in the same format as the bundled `editors.json`.
+    /// Entries replace bundled ones with the same name.
+    #[arg(long, value_name = "FILE")]
+    editors_manifest: Option<PathBuf>,
+
+    /// Where to fetch signed editor manifest updates from, so newly released editors are
+    /// detected without upgrading. Without it only the bundled manifest is used.
+    #[arg(long, value_name = "URL")]
+    manifest_url: Option<String>,
+
+    /// Write home-manager modules for Nix-managed editors into this directory instead of
+    /// printing them
+    #[arg(long, value_name = "DIR")]
+    nix_snippet_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
 }
 
-#[derive(Serialize)]
-struct Heartbeat {
-    #[serde(rename = "type")]
-    kind: String,
-    time: u64,
-    entity: String,
-    language: String,
+#[derive(Subcommand)]
+enum Commands {
+    /// Remove Hackatime from your editors
+    Uninstall,
+    /// Set up editors installed since the last setup, keeping the existing config
+    AddEditors {
+        /// Set up every new editor without asking, as the `watch` schedule does. Editors you
+        /// turned down or removed Hackatime from are left alone.
+        #[arg(long)]
+        quiet: bool,
+    },
+    /// Show your account, today's coding time and what's set up on this machine
+    Status,
+    /// Check for new editors daily in the background and set them up
+    Watch {
+        /// Stop checking
+        #[arg(long, conflicts_with = "print")]
+        disable: bool,
+        /// Print the schedule that would be set up instead of setting it up
+        #[arg(long)]
+        print: bool,
+    },
+    /// Set up a Remote-SSH, devcontainer or Codespaces machine without prompting: writes the
+    /// config and installs the extension into every VS Code, Cursor and Windsurf server here
+    Remote,
+    /// Print a devcontainer.json snippet that sets up Hackatime when the container is created
+    Devcontainer,
 }
 
 fn generate_random_hostname() -> String {
@@ -52,7 +134,28 @@ fn generate_random_hostname() -> String {
         .collect::<String>()
 }
 
-fn send_test_heartbeat(api_key: &str, api_url: &str) -> Result<()> {
+/// Checks the key with the server. Only a rejected key stops setup: offline machines can
+/// still be set up, and the test heartbeat at the end tells whether it worked.
+fn verify_api_key(api_key: &str, api_url: &str, proxy: Option<&str>) -> Result<()> {
+    match HackatimeClient::new(api_url, api_key, proxy)?.current_user() {
+        Ok(user) => println!(
+            "{}",
+            format!("Setting up Hackatime for {}", user.describe()).dimmed()
+        ),
+        Err(e) if e.is::<KeyRejected>() => {
+            eprintln!(
+                "{} {}. Did you copy the command incorrectly?",
+                "Error:".red().bold(),
+                e
+            );
+            std::process::exit(1);
+        }
+        Err(e) => eprintln!("{} Couldn't verify the API key: {}", "Warning:".yellow(), e),
+    }
+    Ok(())
+}
+
+fn send_test_heartbeat(api_key: &str, api_url: &str, proxy: Option<&str>) -> Result<()> {
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
@@ -65,27 +168,12 @@ fn send_test_heartbeat(api_key: &str, api_url: &str) -> Result<()> {
         language: "Text".to_string(),
     };
 
-    let client = Client::new();
-    let response = client
-        .post(format!("{api_url}/users/current/heartbeats"))
-        .bearer_auth(api_key)
-        .json(&vec![heartbeat])
-        .send()?;
-
-    let status = response.status();
-    if status.is_success() {
-        Ok(())
-    } else {
-        let body = response.text().unwrap_or_default();
-        Err(color_eyre::eyre::eyre!(
-            "Test heartbeat failed ({}): {}",
-            status,
-            body
-        ))
-    }
+    HackatimeClient::new(api_url, api_key, proxy)?
+        .send_heartbeat(&heartbeat)
+        .map_err(|e| color_eyre::eyre::eyre!("Test heartbeat failed: {}", e))
 }
 
-fn build_config(api_key: &str, api_url: &str, advanced: bool) -> Result<Ini> {
+fn build_config(api_key: &str, api_url: &str, proxy: Option<&str>, advanced: bool) -> Result<Ini> {
     let theme = ColorfulTheme::default();
     let mut conf = Ini::new();
 
@@ -94,6 +182,9 @@ fn build_config(api_key: &str, api_url: &str, advanced: bool) -> Result<Ini> {
         .set("api_key", api_key)
         .set("heartbeat_rate_limit_seconds", "30")
         .set("exclude_unknown_project", "true");
+    if let Some(proxy) = proxy {
+        conf.with_section(Some("settings")).set("proxy", proxy);
+    }
 
     if advanced {
         let hide_branch = Confirm::with_theme(&theme)
@@ -122,7 +213,156 @@ fn build_config(api_key: &str, api_url: &str, advanced: bool) -> Result<Ini> {
     Ok(conf)
 }
 
-fn validate_api_key(key: &str) -> Result<(), String> {
+fn build_vscode_settings() -> Result<VsCodeSettings> {
+    let theme = ColorfulTheme::default();
+
+    let status_bar_enabled = Confirm::with_theme(&theme)
+        .with_prompt("Show Hackatime in the VS Code status bar?")
+        .default(true)
+        .interact()?;
+
+    let debug = Confirm::with_theme(&theme)
+        .with_prompt("Enable debug logging in VS Code?")
+        .default(false)
+        .interact()?;
+
+    Ok(VsCodeSettings {
+        status_bar_enabled: Some(status_bar_enabled),
+        debug: Some(debug),
+    })
+}
+
+fn config_write_option() -> WriteOption {
+    WriteOption {
+        kv_separator: " = ",
+        ..Default::default()
+    }
+}
+
+fn config_path() -> Result<PathBuf> {
+    Ok(dirs::home_dir()
+        .wrap_err("Could not find home directory")?
+        .join(".wakatime.cfg"))
+}
+
+fn write_config(conf: &Ini, path: &Path) -> Result<()> {
+    journal::record_write(path)?;
+    conf.write_to_file_opt(path, config_write_option())?;
+    println!(
+        "{} {}\n",
+        "✔".green().bold(),
+        format!("Config written to {}", path.display()).green()
+    );
+    Ok(())
+}
+
+/// Editors running on the Windows side of WSL read the Windows config, not the distro's
+fn write_windows_config(conf: &Ini, path: &Path) -> Result<()> {
+    let write = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "You're in WSL. Also write this config to {} for your Windows editors?",
+            path.display()
+        ))
+        .default(true)
+        .interact()?;
+    if !write {
+        return Ok(());
+    }
+
+    write_config(conf, path)
+}
+
+/// Runs on the remote end of Remote-SSH, a devcontainer or a Codespace, often from a
+/// `postCreateCommand` with no terminal attached, so nothing here may prompt
+fn setup_remote(api_key: &str, api_url: &str, proxy: Option<&str>) -> Result<()> {
+    let conf = build_config(api_key, api_url, proxy, false)?;
+    let transaction = journal::begin()?;
+    write_config(&conf, &config_path()?)?;
+
+    let servers: Vec<VsCodeServer> = VsCodeServer::remote()
+        .into_iter()
+        .filter(|s| s.is_installed())
+        .collect();
+    let installed = if servers.is_empty() {
+        println!(
+            "{}",
+            "No VS Code, Cursor or Windsurf server found. Connect from your editor once, then run this again."
+                .dimmed()
+        );
+        Vec::new()
+    } else {
+        install_plugins(servers.iter().map(|s| s as &dyn EditorPlugin).collect())
+    };
+    transaction.commit()?;
+    save_state(api_url, &installed, &[]);
+
+    if let Err(e) = send_test_heartbeat(api_key, api_url, proxy) {
+        eprintln!("{} {}", "Warning:".yellow(), e);
+    }
+    Ok(())
+}
+
+/// The key comes from the host's or Codespaces' `HACKATIME_API_KEY` so it never ends up in the
+/// repository
+fn print_devcontainer_snippet(api_url: &str) -> Result<()> {
+    let command = format!(
+        "curl -fsSL {INSTALL_SCRIPT_URL} | bash -s -- \"$HACKATIME_API_KEY\" '{api_url}' remote"
+    );
+    let snippet = serde_json::json!({
+        "containerEnv": {
+            "HACKATIME_API_KEY": "${localEnv:HACKATIME_API_KEY}"
+        },
+        "postCreateCommand": command,
+        "customizations": {
+            "vscode": {
+                "extensions": ["WakaTime.vscode-wakatime"]
+            }
+        }
+    });
+
+    eprintln!(
+        "{}",
+        "Merge this into your .devcontainer/devcontainer.json, and set HACKATIME_API_KEY on your machine or as a Codespaces secret:\n"
+            .dimmed()
+    );
+    println!("{}", serde_json::to_string_pretty(&snippet)?);
+    Ok(())
+}
+
+/// `initialization_options` takes the key itself rather than a path to a config file, so this
+/// asks again before writing it, since settings.json is often kept in a dotfiles repo
+fn build_zed_settings(api_key: &str, api_url: &str) -> Result<ZedSettings> {
+    let pass_through = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Also put your API key and URL in Zed's settings.json? (for a custom WAKATIME_HOME)",
+        )
+        .default(false)
+        .interact()?;
+    if !pass_through {
+        return Ok(ZedSettings::default());
+    }
+
+    eprintln!(
+        "{} Zed's settings.json would hold your API key in plain text. Don't share it or commit it to a dotfiles repo.",
+        "Warning:".yellow()
+    );
+    let include_key = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Write your API key there anyway? (otherwise only the API URL)")
+        .default(false)
+        .interact()?;
+
+    Ok(ZedSettings {
+        api_url: Some(api_url.to_string()),
+        api_key: include_key.then(|| api_key.to_string()),
+    })
+}
+
+/// Checks `key` looks like an API key for `api_url`: a UUIDv4, which WakaTime prefixes with `waka_`
+fn validate_api_key(key: &str, api_url: &str) -> Result<(), String> {
+    let key = match key.strip_prefix("waka_") {
+        Some(uuid) if api::is_wakatime(api_url) => uuid,
+        _ => key,
+    };
     let uuid = Uuid::try_parse(key)
         .map_err(|_| "API key must be a valid UUID. Did you copy the command incorrectly?")?;
     if uuid.get_version_num() != 4 {
@@ -134,12 +374,47 @@ fn validate_api_key(key: &str) -> Result<(), String> {
 fn main() -> Result<()> {
     color_eyre::install()?;
     let cli = Cli::parse();
+    let mut options = SetupOptions {
+        vscode_user_data_dir: cli.user_data_dir,
+        vscode_extensions_dir: cli.extensions_dir,
+        vscode_family: cli.vscode_family,
+        vsix_gallery_url: cli.gallery_url,
+        vsix_path: cli.vsix,
+        vscode_settings: VsCodeSettings::default(),
+        zed_settings: ZedSettings::default(),
+        jetbrains_plugin_zip: cli.jetbrains_plugin_zip,
+        jetbrains_unpack: cli.jetbrains_unpack,
+        editors_manifest: cli.editors_manifest,
+        manifest_url: cli.manifest_url,
+        nix_snippet_dir: cli.nix_snippet_dir,
+    };
+
+    match cli.command {
+        Some(Commands::Uninstall) => return uninstall(&options),
+        Some(Commands::AddEditors { quiet }) => return add_editors(&options, quiet),
+        Some(Commands::Status) => return status(),
+        Some(Commands::Watch { disable: true, .. }) => return watch::disable(),
+        Some(Commands::Watch { print: true, .. }) => return watch::print(),
+        Some(Commands::Watch { .. }) => return watch::enable(),
+        Some(Commands::Devcontainer) => {
+            return print_devcontainer_snippet(&api::normalize_api_url(&cli.api_url)?);
+        }
+        Some(Commands::Remote) | None => {}
+    }
 
-    if let Err(e) = validate_api_key(&cli.key) {
+    let api_key = cli.key.wrap_err("An API key is required")?;
+    if let Err(e) = validate_api_key(&api_key, &cli.api_url) {
         eprintln!("{} {}", "Error:".red().bold(), e);
         std::process::exit(1);
     }
 
+    let api_url = api::resolve_api_url(&cli.api_url, &api_key, cli.proxy.as_deref())?;
+    verify_api_key(&api_key, &api_url, cli.proxy.as_deref())?;
+
+    if let Some(Commands::Remote) = cli.command {
+        return setup_remote(&api_key, &api_url, cli.proxy.as_deref());
+    }
+
     println!("{}", "Welcome to Hackatime!\n".italic());
 
     let setup_options = vec!["Quick setup", "Advanced setup"];
@@ -151,15 +426,14 @@ fn main() -> Result<()> {
 
     let is_advanced = setup_choice == 1;
 
-    let conf = build_config(&cli.key, &cli.api_url, is_advanced)?;
-
-    let write_opt = WriteOption {
-        kv_separator: " = ",
-        ..Default::default()
-    };
+    let conf = build_config(&api_key, &api_url, cli.proxy.as_deref(), is_advanced)?;
+    if is_advanced {
+        options.vscode_settings = build_vscode_settings()?;
+        options.zed_settings = build_zed_settings(&api_key, &api_url)?;
+    }
 
     let mut config_string = Vec::new();
-    conf.write_to_opt(&mut config_string, write_opt.clone())?;
+    conf.write_to_opt(&mut config_string, config_write_option())?;
     config_string.extend_from_slice(b"\n# help with config: https://github.com/wakatime/wakatime-cli/blob/develop/USAGE.md#ini-config-file");
     let generated_config = String::from_utf8(config_string)?;
 
@@ -181,18 +455,14 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let config_path = dirs::home_dir()
-        .wrap_err("Could not find home directory")?
-        .join(".wakatime.cfg");
+    let transaction = journal::begin()?;
+    write_config(&conf, &config_path()?)?;
 
-    conf.write_to_file_opt(&config_path, write_opt)?;
-    println!(
-        "{} {}\n",
-        "✔".green().bold(),
-        format!("Config written to {}", config_path.display()).green()
-    );
+    if let Some(windows_home) = wsl::Wsl::detect().and_then(|wsl| wsl.windows_home()) {
+        write_windows_config(&conf, &windows_home.join(".wakatime.cfg"))?;
+    }
 
-    let all_editors = editor_plugins::all_editors();
+    let all_editors = editor_plugins::all_editors(&options)?;
     let installed_editors: Vec<_> = all_editors
         .into_par_iter()
         .filter(|e| e.is_installed())
@@ -200,30 +470,51 @@ fn main() -> Result<()> {
 
     if installed_editors.is_empty() {
         println!("{}", "No supported editors found.".dimmed());
+        transaction.commit()?;
+        save_state(&api_url, &[], &[]);
         return Ok(());
     }
 
-    let editor_names: Vec<String> = installed_editors.iter().map(|e| e.name()).collect();
-    let all_selected: Vec<bool> = vec![true; editor_names.len()];
+    // On a re-run, point out editors that weren't around last time
+    let state = State::load();
+    let editor_names: Vec<String> = installed_editors
+        .iter()
+        .map(|e| {
+            let name = e.name();
+            if state.last_setup.is_some() && !state.is_set_up(&name) {
+                format!("{name} {}", "(new)".green())
+            } else {
+                name
+            }
+        })
+        .collect();
+    let defaults: Vec<bool> = installed_editors
+        .iter()
+        .map(|e| e.selected_by_default())
+        .collect();
     let selections = MultiSelect::with_theme(&ColorfulTheme::default())
         .with_prompt("What editors should I install Hackatime to? (space to select/unselect)")
         .items(&editor_names)
-        .defaults(&all_selected)
+        .defaults(&defaults)
         .interact()?;
     let has_editors_to_install = !selections.is_empty();
+    let declined = declined(&installed_editors, &selections);
 
-    if has_editors_to_install {
+    let installed = if has_editors_to_install {
         let selected_editors: Vec<_> = selections
             .into_iter()
-            .map(|i| &installed_editors[i])
+            .map(|i| installed_editors[i].as_ref())
             .collect();
-        install_plugins(selected_editors);
+        install_plugins(selected_editors)
     } else {
         println!(
             "\n{}",
             "No editors selected, skipping editor plugin installation.".dimmed()
         );
-    }
+        Vec::new()
+    };
+    transaction.commit()?;
+    save_state(&api_url, &installed, &declined);
 
     println!(
         "Instructions for other editors: {}",
@@ -239,30 +530,305 @@ fn main() -> Result<()> {
             .underline()
     );
 
-    if let Err(e) = send_test_heartbeat(&cli.key, &cli.api_url) {
+    if let Err(e) = send_test_heartbeat(&api_key, &api_url, cli.proxy.as_deref()) {
         eprintln!("{} {}", "Warning:".yellow(), e);
     }
 
     Ok(())
 }
 
-fn install_plugins(selected_editors: Vec<&Box<dyn EditorPlugin>>) {
+/// The settings an earlier setup wrote to `~/.wakatime.cfg`
+struct ExistingConfig {
+    api_key: String,
+    api_url: String,
+    proxy: Option<String>,
+}
+
+/// Reads `~/.wakatime.cfg`, provided it has a usable key
+fn existing_config() -> Result<ExistingConfig> {
+    read_existing_config(&config_path()?)
+}
+
+fn read_existing_config(path: &Path) -> Result<ExistingConfig> {
+    let conf = Ini::load_from_file(path).map_err(|e| {
+        color_eyre::eyre::eyre!(
+            "No usable config at {} ({}). Run the full setup first.",
+            path.display(),
+            e
+        )
+    })?;
+    let setting = |key| conf.get_from(Some("settings"), key);
+    let api_key = setting("api_key").unwrap_or_default();
+    let api_url = setting("api_url").unwrap_or(DEFAULT_API_URL);
+    validate_api_key(api_key, api_url)
+        .map_err(|e| color_eyre::eyre::eyre!("{} has no valid api_key: {}", path.display(), e))?;
+
+    Ok(ExistingConfig {
+        api_key: api_key.to_string(),
+        api_url: api_url.to_string(),
+        proxy: setting("proxy").map(str::to_string),
+    })
+}
+
+/// Installed editors that neither the state file nor the editor itself know to have the
+/// plugin yet, leaving out ones the user turned down if `skip_declined`
+fn unconfigured_editors(
+    options: &SetupOptions,
+    skip_declined: bool,
+) -> Result<Vec<Box<dyn EditorPlugin>>> {
+    let state = State::load();
+    Ok(editor_plugins::all_editors(options)?
+        .into_par_iter()
+        .filter(|e| e.is_installed())
+        .filter(|e| !state.is_set_up(&e.name()) && e.plugin_version().is_none())
+        .filter(|e| !skip_declined || !state.is_skipped(&e.name()))
+        .collect())
+}
+
+/// Names of the editors that weren't selected
+fn declined(editors: &[Box<dyn EditorPlugin>], selections: &[usize]) -> Vec<String> {
+    editors
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !selections.contains(i))
+        .map(|(_, e)| e.name())
+        .collect()
+}
+
+/// With `quiet`, nothing is asked: the editors setup would pre-select are installed and the
+/// user is notified instead, since this is how the `watch` schedule runs
+fn add_editors(options: &SetupOptions, quiet: bool) -> Result<()> {
+    let api_url = existing_config()?.api_url;
+    let new_editors = unconfigured_editors(options, quiet)?;
+
+    if new_editors.is_empty() {
+        if !quiet {
+            println!("{}", "No new editors found, everything is set up.".dimmed());
+        }
+        return Ok(());
+    }
+
+    let defaults: Vec<bool> = new_editors
+        .iter()
+        .map(|e| e.selected_by_default())
+        .collect();
+    let selections: Vec<usize> = if quiet {
+        (0..new_editors.len()).filter(|&i| defaults[i]).collect()
+    } else {
+        let editor_names: Vec<String> = new_editors.iter().map(|e| e.name()).collect();
+        MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Found new editors. Which should I install Hackatime to?")
+            .items(&editor_names)
+            .defaults(&defaults)
+            .interact()?
+    };
+    let declined = declined(&new_editors, &selections);
+
+    let transaction = journal::begin()?;
+    let installed = install_plugins(
+        selections
+            .into_iter()
+            .map(|i| new_editors[i].as_ref())
+            .collect(),
+    );
+    transaction.commit()?;
+    save_state(&api_url, &installed, &declined);
+
+    if quiet && !installed.is_empty() {
+        let names: Vec<String> = installed.iter().map(|e| e.name()).collect();
+        watch::notify(&format!(
+            "Hackatime is now set up in {}. Restart to start tracking.",
+            names.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+fn status() -> Result<()> {
+    let config = existing_config()?;
+    let client = HackatimeClient::new(&config.api_url, &config.api_key, config.proxy.as_deref())?;
+    let failed = |e: color_eyre::Report| format!("{} {}", "✘".red(), e);
+
+    println!("{} {}", "Server:".bold(), config.api_url);
+    let account = client.current_user().map(|user| user.describe());
+    println!("{} {}", "Account:".bold(), account.unwrap_or_else(failed));
+
+    let today = client.status_bar_today().map(|today| {
+        if today.grand_total.total_seconds > 0.0 {
+            today.grand_total.text
+        } else {
+            "nothing yet".dimmed().to_string()
+        }
+    });
+    println!("{} {}", "Today:".bold(), today.unwrap_or_else(failed));
+
+    let week = client.stats("last_7_days").map(|stats| {
+        let top = |entries: &[api::StatsEntry]| {
+            entries
+                .iter()
+                .take(3)
+                .map(|e| e.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!(
+            "{} {} {}, {} {}",
+            stats.human_readable_total.unwrap_or_default(),
+            "mostly".dimmed(),
+            top(&stats.languages),
+            "in".dimmed(),
+            top(&stats.editors)
+        )
+    });
+    println!("{} {}", "Last 7 days:".bold(), week.unwrap_or_else(failed));
+
+    let state = State::load();
+    println!("\n{}", "Set up on this machine:".bold());
+    if state.editors.is_empty() {
+        println!("  {}", "nothing recorded yet".dimmed());
+    }
+    for editor in &state.editors {
+        let version = editor.plugin_version.as_deref().unwrap_or_default();
+        let path = editor
+            .path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        println!(
+            "  {} {} {} {}",
+            "✔".green(),
+            editor.name,
+            version.cyan(),
+            path.dimmed()
+        );
+    }
+    let watch = if watch::is_enabled() {
+        "on".green()
+    } else {
+        "off".dimmed()
+    };
+    println!("{} {}", "Checking for new editors daily:".bold(), watch);
+
+    Ok(())
+}
+
+fn uninstall(options: &SetupOptions) -> Result<()> {
+    let runs = journal::Run::recorded();
+    if !runs.is_empty() {
+        let changes: usize = runs.iter().map(journal::Run::change_count).sum();
+        let replay = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Undo everything earlier setups changed? ({} changes over {} runs)",
+                changes,
+                runs.len()
+            ))
+            .default(true)
+            .interact()?;
+
+        if replay {
+            for run in runs {
+                if let Err(e) = run.replay() {
+                    eprintln!("{} {}", "Warning:".yellow(), e);
+                }
+            }
+            if let Err(e) = State::remove() {
+                eprintln!("{} {}", "Warning:".yellow(), e);
+            }
+            if watch::is_enabled()
+                && let Err(e) = watch::disable()
+            {
+                eprintln!("{} {}", "Warning:".yellow(), e);
+            }
+            println!("{} {}", "✔".green(), "Setup changes undone".green());
+            return Ok(());
+        }
+    }
+
+    let installed_editors: Vec<_> = editor_plugins::all_editors(options)?
+        .into_par_iter()
+        .filter(|e| e.is_installed())
+        .collect();
+
+    if installed_editors.is_empty() {
+        println!("{}", "No supported editors found.".dimmed());
+        return Ok(());
+    }
+
+    let editor_names: Vec<String> = installed_editors.iter().map(|e| e.name()).collect();
+    let selections = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("What editors should I remove Hackatime from? (space to select/unselect)")
+        .items(&editor_names)
+        .interact()?;
+
+    if selections.is_empty() {
+        println!("{}", "No editors selected, nothing to do.".dimmed());
+        return Ok(());
+    }
+
+    let mut state = State::load();
+    for editor in selections.into_iter().map(|i| &installed_editors[i]) {
+        let name = editor.name();
+        let pb = ProgressBar::new_spinner();
+        pb.set_message(format!("Removing from {name}..."));
+        pb.enable_steady_tick(std::time::Duration::from_millis(80));
+
+        match editor.uninstall() {
+            Ok(()) => {
+                pb.finish_with_message(format!("{} Removed from {}", "✔".green(), name));
+                state.skip(&name);
+            }
+            Err(e) => pb.finish_with_message(format!("{} {} failed: {}", "✘".red(), name, e)),
+        }
+    }
+
+    state.save()
+}
+
+/// Installs into each editor in turn, returning the ones that succeeded
+fn install_plugins(selected_editors: Vec<&dyn EditorPlugin>) -> Vec<&dyn EditorPlugin> {
+    let mut installed = Vec::new();
     for editor in selected_editors {
+        // The run is about to be undone, so don't start on anything else
+        if journal::interrupted() {
+            break;
+        }
         let name = editor.name();
         let pb = ProgressBar::new_spinner();
         pb.set_message(format!("Installing for {name}..."));
         pb.enable_steady_tick(std::time::Duration::from_millis(80));
 
+        // A failed editor is put back the way it was rather than left half set up
+        let checkpoint = journal::checkpoint();
         match editor.install() {
-            Ok(()) => pb.finish_with_message(format!("{} Installed for {}", "✔".green(), name)),
-            Err(e) => pb.finish_with_message(format!("{} {} failed: {}", "✘".red(), name, e)),
+            Ok(()) => {
+                pb.finish_with_message(format!("{} Installed for {}", "✔".green(), name));
+                installed.push(editor);
+            }
+            Err(e) => {
+                pb.finish_with_message(format!("{} {} failed: {}", "✘".red(), name, e));
+                if let Err(e) = journal::rollback_to(checkpoint) {
+                    eprintln!("{} {}", "Warning:".yellow(), e);
+                }
+            }
         }
     }
 
-    println!(
-        "\n\n{}",
-        "Done! You can now code in your editor to track your time.".bold()
-    );
+    if !journal::interrupted() {
+        println!(
+            "\n\n{}",
+            "Done! You can now code in your editor to track your time.".bold()
+        );
+    }
+    installed
+}
+
+/// Remembers what this run set up. Failing to is no reason to fail the setup itself.
+fn save_state(api_url: &str, installed: &[&dyn EditorPlugin], declined: &[String]) {
+    let mut state = State::load();
+    state.record_setup(api_url, installed, declined);
+    if let Err(e) = state.save() {
+        eprintln!("{} {}", "Warning:".yellow(), e);
+    }
 }
 
 fn print_ini(ini: &str) -> Result<()> {
@@ -315,3 +881,81 @@ fn print_ini(ini: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const KEY: &str = "12345678-1234-4234-8234-123456789abc";
+
+    struct FakeEditor(&'static str);
+
+    impl EditorPlugin for FakeEditor {
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn is_installed(&self) -> bool {
+            true
+        }
+
+        fn install(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reads_existing_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".wakatime.cfg");
+        fs::write(
+            &path,
+            format!("[settings]\napi_url = https://example.com/api/v1\napi_key = {KEY}\nproxy = http://proxy:3128\n"),
+        )
+        .unwrap();
+
+        let config = read_existing_config(&path).unwrap();
+        assert_eq!(config.api_key, KEY);
+        assert_eq!(config.api_url, "https://example.com/api/v1");
+        assert_eq!(config.proxy.as_deref(), Some("http://proxy:3128"));
+
+        fs::write(&path, format!("[settings]\napi_key = {KEY}\n")).unwrap();
+        let config = read_existing_config(&path).unwrap();
+        assert_eq!(config.api_url, DEFAULT_API_URL);
+        assert_eq!(config.proxy, None);
+    }
+
+    #[test]
+    fn rejects_config_without_usable_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".wakatime.cfg");
+        assert!(read_existing_config(&path).is_err());
+
+        fs::write(&path, "[settings]\napi_key = not-a-key\n").unwrap();
+        let error = read_existing_config(&path).err().unwrap().to_string();
+        assert!(error.contains("has no valid api_key"), "{error}");
+    }
+
+    #[test]
+    fn accepts_waka_keys_only_for_wakatime() {
+        let waka_key = format!("waka_{KEY}");
+        assert!(validate_api_key(KEY, DEFAULT_API_URL).is_ok());
+        assert!(validate_api_key(&waka_key, "wakatime").is_ok());
+        assert!(validate_api_key(&waka_key, "https://api.wakatime.com/api/v1").is_ok());
+        assert!(validate_api_key(&waka_key, DEFAULT_API_URL).is_err());
+        assert!(validate_api_key("waka_not-a-key", "wakatime").is_err());
+    }
+
+    #[test]
+    fn lists_unselected_editors_as_declined() {
+        let editors: Vec<Box<dyn EditorPlugin>> = vec![
+            Box::new(FakeEditor("Cursor")),
+            Box::new(FakeEditor("Zed")),
+            Box::new(FakeEditor("Terminal")),
+        ];
+        assert_eq!(declined(&editors, &[1]), ["Cursor", "Terminal"]);
+        assert!(declined(&editors, &[0, 1, 2]).is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+
+use crate::editor_plugins::EditorPlugin;
+
+/// What earlier runs set up and where, kept in `~/.wakatime/hackatime-setup.json`
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct State {
+    /// Version of hackatime-setup that last wrote this
+    pub tool_version: String,
+    pub api_url: Option<String>,
+    pub first_setup: Option<u64>,
+    pub last_setup: Option<u64>,
+    pub editors: Vec<EditorRecord>,
+    /// Editors the user turned down or removed Hackatime from, which background checks leave
+    /// alone
+    pub skipped: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EditorRecord {
+    pub name: String,
+    /// Where the plugin or its configuration went
+    pub path: Option<PathBuf>,
+    pub plugin_version: Option<String>,
+    pub installed_at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+impl State {
+    fn path() -> Option<PathBuf> {
+        dirs::home_dir().map(|h| h.join(".wakatime/hackatime-setup.json"))
+    }
+
+    /// The saved state, or an empty one if there is none yet or it can't be read
+    pub fn load() -> Self {
+        Self::path()
+            .map(|path| Self::load_from(&path))
+            .unwrap_or_default()
+    }
+
+    fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| eyre!("Could not find home directory"))?;
+        self.save_to(&path)
+    }
+
+    fn save_to(&mut self, path: &Path) -> Result<()> {
+        self.tool_version = env!("CARGO_PKG_VERSION").to_string();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| eyre!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Forgets everything, once all of it has been undone
+    pub fn remove() -> Result<()> {
+        match Self::path().filter(|p| p.exists()) {
+            Some(path) => fs::remove_file(&path)
+                .map_err(|e| eyre!("Failed to remove {}: {}", path.display(), e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a finished setup run, the editors it installed into and the ones the user
+    /// turned down
+    pub fn record_setup(
+        &mut self,
+        api_url: &str,
+        editors: &[&dyn EditorPlugin],
+        declined: &[String],
+    ) {
+        let now = now();
+        self.api_url = Some(api_url.to_string());
+        self.first_setup.get_or_insert(now);
+        self.last_setup = Some(now);
+
+        for editor in editors {
+            let name = editor.name();
+            self.forget(&name);
+            self.editors.push(EditorRecord {
+                name,
+                path: editor.install_path(),
+                plugin_version: editor.plugin_version(),
+                installed_at: now,
+            });
+        }
+        for name in declined {
+            self.skip(name);
+        }
+    }
+
+    pub fn forget(&mut self, editor_name: &str) {
+        self.editors.retain(|e| e.name != editor_name);
+        self.skipped.retain(|name| name != editor_name);
+    }
+
+    /// Forgets the editor and keeps background checks from setting it up again
+    pub fn skip(&mut self, editor_name: &str) {
+        self.forget(editor_name);
+        self.skipped.push(editor_name.to_string());
+    }
+
+    pub fn is_skipped(&self, editor_name: &str) -> bool {
+        self.skipped.iter().any(|name| name == editor_name)
+    }
+
+    pub fn is_set_up(&self, editor_name: &str) -> bool {
+        self.editors.iter().any(|e| e.name == editor_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeEditor(&'static str);
+
+    impl EditorPlugin for FakeEditor {
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn is_installed(&self) -> bool {
+            true
+        }
+
+        fn install(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn install_path(&self) -> Option<PathBuf> {
+            Some(PathBuf::from("/config").join(self.0))
+        }
+
+        fn plugin_version(&self) -> Option<String> {
+            Some("1.0.0".to_string())
+        }
+    }
+
+    #[test]
+    fn records_installed_and_declined_editors() {
+        let mut state = State::default();
+        state.record_setup(
+            "https://hackatime.hackclub.com/api/hackatime/v1",
+            &[&FakeEditor("Cursor"), &FakeEditor("Zed")],
+            &["PyCharm".to_string()],
+        );
+        let first_setup = state.first_setup;
+
+        assert!(state.is_set_up("Cursor"));
+        assert!(state.is_set_up("Zed"));
+        assert!(state.is_skipped("PyCharm"));
+        assert_eq!(state.editors[0].path, Some(PathBuf::from("/config/Cursor")));
+        assert_eq!(state.editors[0].plugin_version.as_deref(), Some("1.0.0"));
+
+        // Setting up a declined editor later un-skips it, and re-recording doesn't duplicate
+        state.record_setup(
+            "https://hackatime.hackclub.com/api/hackatime/v1",
+            &[&FakeEditor("PyCharm"), &FakeEditor("Zed")],
+            &[],
+        );
+        assert!(!state.is_skipped("PyCharm"));
+        assert!(state.is_set_up("PyCharm"));
+        assert_eq!(state.editors.len(), 3);
+        assert_eq!(state.first_setup, first_setup);
+    }
+
+    #[test]
+    fn skipping_forgets_the_editor() {
+        let mut state = State::default();
+        state.record_setup("https://example.com", &[&FakeEditor("Zed")], &[]);
+        state.skip("Zed");
+        state.skip("Zed");
+
+        assert!(!state.is_set_up("Zed"));
+        assert_eq!(state.skipped, ["Zed"]);
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".wakatime/hackatime-setup.json");
+        assert!(State::load_from(&path).editors.is_empty());
+
+        let mut state = State::default();
+        state.record_setup("https://example.com", &[&FakeEditor("Zed")], &[]);
+        state.save_to(&path).unwrap();
+
+        let loaded = State::load_from(&path);
+        assert_eq!(loaded.tool_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(loaded.api_url.as_deref(), Some("https://example.com"));
+        assert!(loaded.is_set_up("Zed"));
+
+        fs::write(&path, "not json").unwrap();
+        assert!(State::load_from(&path).editors.is_empty());
+    }
+}
@@ -0,0 +1,397 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use color_eyre::{
+    Result,
+    eyre::{ContextCompat, eyre},
+};
+use colored::Colorize;
+
+const UNIT_NAME: &str = "hackatime-setup";
+const LAUNCHD_LABEL: &str = "com.hackclub.hackatime-setup";
+const TASK_NAME: &str = "Hackatime Setup";
+
+/// What the scheduled job runs
+const JOB_ARGS: &[&str] = &["add-editors", "--quiet"];
+
+/// A file the schedule consists of
+struct ScheduleFile {
+    path: PathBuf,
+    content: String,
+}
+
+/// Where the scheduled job runs the tool from. The installers run it from a temp dir that's
+/// gone by the time the job fires.
+fn installed_binary() -> Option<PathBuf> {
+    let name = format!("hackatime_setup{}", std::env::consts::EXE_SUFFIX);
+    dirs::home_dir().map(|h| h.join(".wakatime/hackatime-setup/bin").join(name))
+}
+
+pub fn is_enabled() -> bool {
+    installed_binary().is_some_and(|b| b.exists())
+}
+
+/// Unit files, plists and the like for this OS, under `home` and `config_dir`. Windows keeps
+/// its schedule in Task Scheduler rather than in files.
+fn schedule_files(exe: &Path, home: &Path, config_dir: &Path) -> Vec<ScheduleFile> {
+    if cfg!(target_os = "linux") {
+        systemd_units(exe, &config_dir.join("systemd/user"))
+    } else if cfg!(target_os = "macos") {
+        vec![launch_agent(exe, home)]
+    } else {
+        Vec::new()
+    }
+}
+
+/// `schedule_files` for the current user
+fn user_schedule_files(exe: &Path) -> Result<Vec<ScheduleFile>> {
+    let home = dirs::home_dir().wrap_err("Could not find home directory")?;
+    let config_dir = dirs::config_dir().unwrap_or_else(|| home.join(".config"));
+    Ok(schedule_files(exe, &home, &config_dir))
+}
+
+/// The job as a single command line, with the binary's path quoted
+fn job_command_line(exe: &Path) -> String {
+    std::iter::once(format!("\"{}\"", exe.display()))
+        .chain(JOB_ARGS.iter().map(|arg| arg.to_string()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn systemd_units(exe: &Path, units: &Path) -> Vec<ScheduleFile> {
+    vec![
+        ScheduleFile {
+            path: units.join(format!("{UNIT_NAME}.service")),
+            content: format!(
+                "[Unit]\nDescription=Set up Hackatime in newly installed editors\n\n[Service]\nType=oneshot\nExecStart={}\n",
+                job_command_line(exe)
+            ),
+        },
+        ScheduleFile {
+            path: units.join(format!("{UNIT_NAME}.timer")),
+            content: "[Unit]\nDescription=Check daily for new editors to set up Hackatime in\n\n[Timer]\nOnCalendar=daily\nPersistent=true\nRandomizedDelaySec=1h\n\n[Install]\nWantedBy=timers.target\n".to_string(),
+        },
+    ]
+}
+
+fn launch_agent(exe: &Path, home: &Path) -> ScheduleFile {
+    let arguments: String = std::iter::once(exe.to_string_lossy().into_owned())
+        .chain(JOB_ARGS.iter().map(|arg| arg.to_string()))
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(&arg)))
+        .collect();
+    ScheduleFile {
+        path: home.join(format!("Library/LaunchAgents/{LAUNCHD_LABEL}.plist")),
+        content: format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>StartInterval</key>
+    <integer>86400</integer>
+</dict>
+</plist>
+"#
+        ),
+    }
+}
+
+/// Registers the daily task with Task Scheduler
+fn schtasks_create(exe: &Path) -> Vec<String> {
+    [
+        "schtasks",
+        "/Create",
+        "/F",
+        "/SC",
+        "DAILY",
+        "/TN",
+        TASK_NAME,
+        "/TR",
+        &job_command_line(exe),
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Commands that register the schedule once its files are in place
+fn activation_commands(exe: &Path, files: &[ScheduleFile]) -> Vec<Vec<String>> {
+    let owned = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+    if cfg!(target_os = "linux") {
+        let mut commands = Vec::new();
+        // Catches unit mistakes before systemd quietly refuses to load them. `--user` would
+        // need a running user manager, which the files alone don't.
+        if which::which("systemd-analyze").is_ok() {
+            let mut verify = owned(&["systemd-analyze", "verify"]);
+            verify.extend(files.iter().map(|f| f.path.to_string_lossy().into_owned()));
+            commands.push(verify);
+        }
+        commands.push(owned(&["systemctl", "--user", "daemon-reload"]));
+        commands.push(owned(&[
+            "systemctl",
+            "--user",
+            "enable",
+            "--now",
+            &format!("{UNIT_NAME}.timer"),
+        ]));
+        commands
+    } else if cfg!(target_os = "macos") {
+        files
+            .iter()
+            .map(|f| owned(&["launchctl", "load", "-w", &f.path.to_string_lossy()]))
+            .collect()
+    } else if cfg!(target_os = "windows") {
+        vec![schtasks_create(exe)]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Commands that unregister the schedule before its files are removed
+fn deactivation_commands(files: &[ScheduleFile]) -> Vec<Vec<String>> {
+    let owned = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+    if cfg!(target_os = "linux") {
+        vec![owned(&[
+            "systemctl",
+            "--user",
+            "disable",
+            "--now",
+            &format!("{UNIT_NAME}.timer"),
+        ])]
+    } else if cfg!(target_os = "macos") {
+        files
+            .iter()
+            .map(|f| owned(&["launchctl", "unload", "-w", &f.path.to_string_lossy()]))
+            .collect()
+    } else if cfg!(target_os = "windows") {
+        vec![owned(&["schtasks", "/Delete", "/F", "/TN", TASK_NAME])]
+    } else {
+        Vec::new()
+    }
+}
+
+fn run(command: &[String]) -> Result<()> {
+    let (program, args) = command.split_first().wrap_err("Empty command")?;
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| eyre!("Failed to execute {}: {}", program, e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "`{}` failed: {}",
+            command.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Prints the schedule `enable` would set up, without touching anything
+pub fn print() -> Result<()> {
+    let exe = installed_binary().wrap_err("Could not find home directory")?;
+    let files = user_schedule_files(&exe)?;
+    for file in &files {
+        println!(
+            "{}\n{}",
+            format!("# {}", file.path.display()).dimmed(),
+            file.content
+        );
+    }
+    for command in activation_commands(&exe, &files) {
+        println!("{} {}", "$".dimmed(), command.join(" "));
+    }
+    Ok(())
+}
+
+/// Copies this binary somewhere permanent and schedules a daily quiet `add-editors` run
+pub fn enable() -> Result<()> {
+    let exe = installed_binary().wrap_err("Could not find home directory")?;
+    let current = std::env::current_exe()?;
+    if let Some(parent) = exe.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::canonicalize(&current).ok() != fs::canonicalize(&exe).ok() {
+        fs::copy(&current, &exe)
+            .map_err(|e| eyre!("Failed to copy to {}: {}", exe.display(), e))?;
+    }
+
+    let files = user_schedule_files(&exe)?;
+    for file in &files {
+        if let Some(parent) = file.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&file.path, &file.content)
+            .map_err(|e| eyre!("Failed to write {}: {}", file.path.display(), e))?;
+    }
+
+    let commands = activation_commands(&exe, &files);
+    if commands.is_empty() {
+        return Err(eyre!("Scheduling isn't supported on this OS"));
+    }
+    commands.iter().try_for_each(|command| run(command))?;
+
+    println!(
+        "{} {}",
+        "✔".green(),
+        "Hackatime will check for new editors daily and set them up".green()
+    );
+    Ok(())
+}
+
+/// Removes everything `enable` set up
+pub fn disable() -> Result<()> {
+    let exe = installed_binary().wrap_err("Could not find home directory")?;
+    let files = user_schedule_files(&exe)?;
+
+    // Already-removed schedules make these fail, which is fine
+    for command in deactivation_commands(&files) {
+        let _ = run(&command);
+    }
+    for path in files.iter().map(|f| &f.path).chain([&exe]) {
+        if path.exists() {
+            fs::remove_file(path)
+                .map_err(|e| eyre!("Failed to remove {}: {}", path.display(), e))?;
+        }
+    }
+    if cfg!(target_os = "linux") {
+        let _ = run(&["systemctl", "--user", "daemon-reload"].map(String::from));
+    }
+
+    println!(
+        "{} {}",
+        "✔".green(),
+        "Stopped checking for new editors".green()
+    );
+    Ok(())
+}
+
+/// Shows a desktop notification, for background runs nobody is watching the output of. The
+/// message is printed too, for the job's log.
+pub fn notify(message: &str) {
+    println!("{message}");
+    let notifier = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification \"{}\" with title \"Hackatime\"",
+            message.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        Command::new("osascript").args(["-e", &script]).output()
+    } else if cfg!(target_os = "windows") {
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms; $n = New-Object System.Windows.Forms.NotifyIcon; $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; $n.ShowBalloonTip(10000, 'Hackatime', '{}', 'Info'); Start-Sleep 10; $n.Dispose()",
+            message.replace('\'', "''")
+        );
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .output()
+    } else {
+        Command::new("notify-send")
+            .args(["--app-name=Hackatime", "Hackatime", message])
+            .output()
+    };
+    // Without a desktop session there's nobody to notify
+    notifier.ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn systemd_timer_runs_add_editors_daily() {
+        let units = systemd_units(
+            Path::new("/home/me/.wakatime/hackatime-setup/bin/hackatime_setup"),
+            Path::new("/home/me/.config/systemd/user"),
+        );
+        assert_eq!(
+            units[0].path,
+            Path::new("/home/me/.config/systemd/user/hackatime-setup.service")
+        );
+        assert!(units[0].content.contains(
+            "\nExecStart=\"/home/me/.wakatime/hackatime-setup/bin/hackatime_setup\" add-editors --quiet\n"
+        ));
+        assert!(units[0].content.contains("\nType=oneshot\n"));
+
+        assert_eq!(
+            units[1].path,
+            Path::new("/home/me/.config/systemd/user/hackatime-setup.timer")
+        );
+        assert!(units[1].content.contains("\nOnCalendar=daily\n"));
+        assert!(units[1].content.contains("\nPersistent=true\n"));
+        assert!(units[1].content.contains("\nWantedBy=timers.target\n"));
+    }
+
+    #[test]
+    fn launch_agent_runs_add_editors_daily() {
+        let agent = launch_agent(
+            Path::new("/Users/me/Tools & Apps/hackatime_setup"),
+            Path::new("/Users/me"),
+        );
+        assert_eq!(
+            agent.path,
+            Path::new("/Users/me/Library/LaunchAgents/com.hackclub.hackatime-setup.plist")
+        );
+        assert!(agent.content.contains(
+            "    <array>\n        <string>/Users/me/Tools &amp; Apps/hackatime_setup</string>\n        <string>add-editors</string>\n        <string>--quiet</string>\n    </array>\n"
+        ));
+        assert!(
+            agent
+                .content
+                .contains("<key>Label</key>\n    <string>com.hackclub.hackatime-setup</string>")
+        );
+        assert!(
+            agent
+                .content
+                .contains("<key>StartInterval</key>\n    <integer>86400</integer>")
+        );
+    }
+
+    #[test]
+    fn schtasks_runs_add_editors_daily() {
+        let exe = Path::new(r"C:\Users\me\.wakatime\hackatime-setup\bin\hackatime_setup.exe");
+        let expected_run = format!("\"{}\" add-editors --quiet", exe.display());
+        assert_eq!(
+            schtasks_create(exe),
+            [
+                "schtasks",
+                "/Create",
+                "/F",
+                "/SC",
+                "DAILY",
+                "/TN",
+                "Hackatime Setup",
+                "/TR",
+                &expected_run,
+            ]
+        );
+    }
+
+    #[test]
+    fn schedule_files_use_given_dirs() {
+        let files = schedule_files(
+            Path::new("/bin/hackatime_setup"),
+            Path::new("/home/me"),
+            Path::new("/xdg/config"),
+        );
+        if cfg!(target_os = "linux") {
+            assert!(files[0].path.starts_with("/xdg/config/systemd/user"));
+        } else if cfg!(target_os = "macos") {
+            assert!(files[0].path.starts_with("/home/me/Library/LaunchAgents"));
+        } else {
+            assert!(files.is_empty());
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ini::Ini;
+
+/// Running inside Windows Subsystem for Linux, where editors usually run on the Windows side
+pub struct Wsl {
+    /// Where Windows drives are mounted, `/mnt` unless `wsl.conf` says otherwise
+    mount_root: PathBuf,
+}
+
+impl Wsl {
+    pub fn detect() -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        let distro = std::env::var("WSL_DISTRO_NAME").ok();
+        Self::detect_in(
+            Path::new("/proc"),
+            Path::new("/etc/wsl.conf"),
+            distro.as_deref(),
+        )
+    }
+
+    /// Detection against a given `/proc`, `wsl.conf` and `WSL_DISTRO_NAME`, so all of them can
+    /// be faked
+    pub fn detect_in(proc_root: &Path, wsl_conf: &Path, distro_name: Option<&str>) -> Option<Self> {
+        let distro = distro_name.is_some_and(|d| !d.is_empty());
+        let kernel = std::fs::read_to_string(proc_root.join("version"))
+            .is_ok_and(|v| v.to_ascii_lowercase().contains("microsoft"));
+        if !distro && !kernel {
+            return None;
+        }
+
+        let mount_root = Ini::load_from_file(wsl_conf)
+            .ok()
+            .and_then(|conf| conf.get_from(Some("automount"), "root").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("/mnt"));
+        Some(Self { mount_root })
+    }
+
+    /// `%USERPROFILE%` as seen from Linux, e.g. `/mnt/c/Users/me`
+    pub fn windows_home(&self) -> Option<PathBuf> {
+        self.userprofile_from_interop()
+            .or_else(|| {
+                let user = std::env::var("USER").ok()?;
+                Some(self.mount_root.join("c/Users").join(user))
+            })
+            .filter(|home| home.is_dir())
+    }
+
+    fn userprofile_from_interop(&self) -> Option<PathBuf> {
+        // cmd.exe warns about and ignores Linux working directories, so start it on a drive
+        let output = Command::new("cmd.exe")
+            .args(["/C", "echo %USERPROFILE%"])
+            .current_dir(self.mount_root.join("c"))
+            .output()
+            .ok()
+            .filter(|o| o.status.success())?;
+        self.to_linux_path(String::from_utf8_lossy(&output.stdout).trim())
+    }
+
+    /// Maps `C:\Users\me` to `<mount root>/c/Users/me`
+    fn to_linux_path(&self, windows_path: &str) -> Option<PathBuf> {
+        let (drive, rest) = windows_path.split_once(':')?;
+        if drive.len() != 1 {
+            return None;
+        }
+        let mut path = self.mount_root.join(drive.to_ascii_lowercase());
+        path.extend(rest.split('\\').filter(|part| !part.is_empty()));
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn fake_proc(version: &str) -> tempfile::TempDir {
+        let proc_root = tempfile::tempdir().unwrap();
+        fs::write(proc_root.path().join("version"), version).unwrap();
+        proc_root
+    }
+
+    #[test]
+    fn plain_linux_is_not_wsl() {
+        let proc_root = fake_proc("Linux version 6.8.0-45-generic (buildd@lcy02-amd64-075)");
+        let wsl = Wsl::detect_in(proc_root.path(), Path::new("/nonexistent"), None);
+        assert!(wsl.is_none());
+    }
+
+    #[test]
+    fn detects_wsl_kernel() {
+        let proc_root = fake_proc("Linux version 5.15.153.1-microsoft-standard-WSL2");
+        let wsl = Wsl::detect_in(proc_root.path(), Path::new("/nonexistent"), None).unwrap();
+        assert_eq!(wsl.mount_root, PathBuf::from("/mnt"));
+    }
+
+    #[test]
+    fn detects_wsl_from_distro_name() {
+        let proc_root = tempfile::tempdir().unwrap();
+        let wsl = Wsl::detect_in(proc_root.path(), Path::new("/nonexistent"), Some("Ubuntu"));
+        assert!(wsl.is_some());
+        let wsl = Wsl::detect_in(proc_root.path(), Path::new("/nonexistent"), Some(""));
+        assert!(wsl.is_none());
+    }
+
+    #[test]
+    fn reads_automount_root_from_wsl_conf() {
+        let proc_root = fake_proc("Linux version 5.15.153.1-microsoft-standard-WSL2");
+        let wsl_conf = proc_root.path().join("wsl.conf");
+        fs::write(&wsl_conf, "[automount]\nenabled = true\nroot = /win/\n").unwrap();
+
+        let wsl = Wsl::detect_in(proc_root.path(), &wsl_conf, None).unwrap();
+        assert_eq!(wsl.mount_root, PathBuf::from("/win/"));
+        assert_eq!(
+            wsl.to_linux_path(r"D:\Projects"),
+            Some(PathBuf::from("/win/d/Projects"))
+        );
+    }
+
+    #[test]
+    fn maps_windows_paths_under_the_mount_root() {
+        let wsl = Wsl {
+            mount_root: PathBuf::from("/mnt"),
+        };
+        assert_eq!(
+            wsl.to_linux_path(r"C:\Users\me"),
+            Some(PathBuf::from("/mnt/c/Users/me"))
+        );
+        assert_eq!(
+            wsl.to_linux_path(r"C:\Users\me\"),
+            Some(PathBuf::from("/mnt/c/Users/me"))
+        );
+        assert_eq!(wsl.to_linux_path(r"\\server\share"), None);
+        assert_eq!(wsl.to_linux_path("%USERPROFILE%"), None);
+    }
+}